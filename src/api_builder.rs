@@ -17,23 +17,33 @@
 // IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
 // CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
-use crate::planner::Query;
-use anyhow::{Context, Result};
+use crate::config::Config;
+use crate::parser;
+use anyhow::{anyhow, Context, Result};
 use k8s_openapi::api::apps::v1::Deployment;
 use k8s_openapi::api::core::v1::Pod;
+use kube::config::KubeConfigOptions;
 use kube::Api;
 use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::time::Duration;
 
 /// A high level wrapper for kube::Api struct
-pub struct ApiBuilder<'a> {
+pub struct ApiBuilder {
     /// The name of the kubeconfig context to use
     context: Option<String>,
 
     /// Show only from this namespace
     namespace: Option<String>,
 
-    //queries: &'a Vec<Query>,
-    queries: Option<&'a [Query]>,
+    /// The kubeconfig files to merge instead of the default ones
+    kubeconfigs: Vec<PathBuf>,
+
+    /// How long the API server is waited for
+    timeout: Option<Duration>,
+
+    /// The client of the context, shared by the APIs
+    client: Option<kube::Client>,
 
     /// Api::Namespaced Deployment value
     deployment: Option<Api<Deployment>>,
@@ -42,53 +52,75 @@ pub struct ApiBuilder<'a> {
     pod: Option<Api<Pod>>,
 }
 
-impl<'a> Default for ApiBuilder<'a> {
+impl Default for ApiBuilder {
     fn default() -> Self {
         ApiBuilder {
             context: None,
             namespace: Option::from("default".to_string()),
-            queries: None,
+            kubeconfigs: vec![],
+            timeout: None,
+            client: None,
             deployment: None,
             pod: None,
         }
     }
 }
 
-impl<'a> ApiBuilder<'a> {
+impl ApiBuilder {
     /// Creates a new API and returns default states
-    pub fn new() -> ApiBuilder<'a> {
+    pub fn new() -> ApiBuilder {
         ApiBuilder::default()
     }
 
     /// Creates a new builder-style object to manufacture a `API`
-    pub fn builder() -> ApiBuilder<'a> {
+    pub fn builder() -> ApiBuilder {
         ApiBuilder::new()
     }
 
     /// Set the given context
-    pub fn context(mut self, ctx: String) -> ApiBuilder<'a> {
+    pub fn context(mut self, ctx: String) -> ApiBuilder {
         self.context = Option::from(ctx);
         self
     }
 
     /// Set the given namespace
-    pub fn namespace(mut self, ns: String) -> ApiBuilder<'a> {
+    pub fn namespace(mut self, ns: String) -> ApiBuilder {
         self.namespace = Option::from(ns);
         self
     }
 
-    pub fn queries(mut self, queries: &'a [Query]) -> ApiBuilder<'a> {
-        self.queries = Option::from(queries);
+    /// Set the kubeconfig files and the request timeout of the given configuration
+    pub fn config(mut self, config: &Config) -> ApiBuilder {
+        self.kubeconfigs = config.kubeconfigs.clone();
+        self.timeout = config.request_timeout;
         self
     }
 
-    /// Try build the whole API or throw a panic
-    pub(crate) async fn build(mut self) -> Result<ApiBuilder<'a>> {
-        let client_config = kube::Config::from_kubeconfig(&kube::config::KubeConfigOptions {
-            context: Some(self.context.clone().unwrap()),
-            ..Default::default()
-        })
-        .await?;
+    /// Try build the whole API or throw a panic. The current context of the kubeconfig is used,
+    /// if there is no context given.
+    pub(crate) async fn build(mut self) -> Result<ApiBuilder> {
+        // the same kubeconfig as the one that the contexts are validated against
+        let kubeconfig = parser::parse_kubeconfig(&self.kubeconfigs);
+        if self.context.is_none() {
+            self.context = Option::from(kubeconfig.current_context.clone().ok_or_else(|| {
+                anyhow!("There is no current-context in your KUBECONFIG, FROM is required")
+            })?);
+        }
+
+        // the service account of the pod is used for the in-cluster context
+        let mut client_config = match parser::is_in_cluster(&kubeconfig, self.get_context()) {
+            true => kube::Config::from_cluster_env()?,
+            false => {
+                let options = KubeConfigOptions {
+                    context: self.context.clone(),
+                    ..Default::default()
+                };
+                kube::Config::from_custom_kubeconfig(kubeconfig, &options).await?
+            }
+        };
+        if self.timeout.is_some() {
+            client_config.timeout = self.timeout;
+        }
 
         match kube::Client::try_from(client_config)
             .with_context(|| "failed to create the kube client with context".to_string())
@@ -98,8 +130,11 @@ impl<'a> ApiBuilder<'a> {
                     c.clone(),
                     self.namespace.clone().unwrap().as_str(),
                 ));
-                self.pod =
-                    Option::from(Api::namespaced(c, self.namespace.clone().unwrap().as_str()));
+                self.pod = Option::from(Api::namespaced(
+                    c.clone(),
+                    self.namespace.clone().unwrap().as_str(),
+                ));
+                self.client = Option::from(c);
             }
             Err(e) => {
                 panic!("an error occurred during creating kube client: {:?}", e)
@@ -109,19 +144,23 @@ impl<'a> ApiBuilder<'a> {
         Ok(self)
     }
 
-    pub fn get_context(&'a self) -> &String {
+    pub fn get_context(&self) -> &String {
         self.context.as_ref().unwrap()
     }
 
-    pub fn get_namespace(&'a self) -> &String {
+    pub fn get_namespace(&self) -> &String {
         self.namespace.as_ref().unwrap()
     }
 
-    pub fn get_deployment(&'a self) -> &Api<Deployment> {
+    pub fn get_client(&self) -> &kube::Client {
+        self.client.as_ref().unwrap()
+    }
+
+    pub fn get_deployment(&self) -> &Api<Deployment> {
         self.deployment.as_ref().unwrap()
     }
 
-    pub fn get_pod(&'a self) -> &Api<Pod> {
+    pub fn get_pod(&self) -> &Api<Pod> {
         self.pod.as_ref().unwrap()
     }
 }
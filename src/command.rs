@@ -0,0 +1,170 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+use crate::config::Config;
+use crate::executor::Executor;
+use crate::parser;
+use crate::printer::Printer;
+use crate::result;
+use crate::schema::Schema;
+use anyhow::{anyhow, Result};
+use prettytable::{Cell, Row, Table};
+
+/// Prints how the given query is run, without calling the API server
+pub fn explain(executor: &Executor, sql: &str) {
+    let api_queries = executor.parse(sql);
+
+    let projections = match api_queries.projections.is_empty() {
+        true => result::default_projections(),
+        false => api_queries.projections.clone(),
+    };
+
+    match api_queries.contexts.is_empty() {
+        true => println!("Contexts:     (the current context)"),
+        false => println!("Contexts:     {}", api_queries.contexts.join(", ")),
+    }
+    println!("Namespaces:   {}", api_queries.namespaces.join(", "));
+    println!(
+        "Columns:      {}",
+        projections
+            .iter()
+            .map(|x| x.name.clone())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+    if api_queries.distinct {
+        println!("Distinct:     yes");
+    }
+    if !api_queries.group_by.is_empty() {
+        println!(
+            "Group by:     {}",
+            api_queries
+                .group_by
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+    }
+    if !api_queries.order_by.is_empty() {
+        println!(
+            "Order by:     {}",
+            api_queries
+                .order_by
+                .iter()
+                .map(|x| format!("{} {}", x.expr, if x.asc { "ASC" } else { "DESC" }))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+    }
+
+    println!();
+    println!("Queries:");
+    for (i, q) in api_queries.queries.iter().enumerate() {
+        let key = q
+            .key
+            .as_ref()
+            .map(|k| format!("{} ", k))
+            .unwrap_or_default();
+        println!("{:>3}. {}{} WHERE {}", i + 1, key, q.kind, q.predicate);
+        match q.field_selector() {
+            Some(f) => println!("     --field-selector {}", f),
+            None => println!("     every object is listed and filtered by kubesql"),
+        }
+    }
+
+    // the current context is queried if there is no context given
+    let contexts = api_queries.contexts.len().max(1);
+    let calls = api_queries.queries.len() * contexts * api_queries.namespaces.len();
+    println!();
+    println!(
+        "API calls:    {} ({} queries x {} contexts x {} namespaces, {} at once)",
+        calls,
+        api_queries.queries.len(),
+        contexts,
+        api_queries.namespaces.len(),
+        executor.config().concurrency
+    );
+}
+
+/// Prints the fields of the given kind or field path: i.e. 'pod.spec.containers', from the
+/// OpenAPI schemas of the configured context
+pub async fn describe(executor: &mut Executor, target: &str) -> Result<()> {
+    let context = executor.config().context.clone();
+    let namespace = executor.config().namespace.clone();
+    let api = executor.api(context.as_deref(), &namespace).await?;
+    let (schema, _) = Schema::fetch(executor.apis()[api].get_client()).await?;
+
+    let (kind, path) = target.split_once('.').unwrap_or((target, ""));
+    let node = schema
+        .get(kind, path)
+        .ok_or_else(|| anyhow!("Unknown kind or field: '{}'", target))?;
+
+    println!("{} <{}>", target.to_lowercase(), node.type_name);
+    if !node.description.is_empty() {
+        println!();
+        println!("{}", node.description);
+    }
+
+    let mut fields = node;
+    while let Some(items) = &fields.items {
+        fields = items;
+    }
+    if fields.properties.is_empty() {
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_format(Printer::plain_format());
+    table.set_titles(Row::new(vec![Cell::new("FIELD"), Cell::new("TYPE")]));
+    for (name, child) in &fields.properties {
+        table.add_row(Row::new(vec![Cell::new(name), Cell::new(&child.type_name)]));
+    }
+    println!();
+    table.printstd();
+
+    Ok(())
+}
+
+/// Prints the contexts of the kubeconfig, marking the one that is used by default
+pub fn contexts(config: &Config) {
+    let kubeconfig = parser::parse_kubeconfig(&config.kubeconfigs);
+    let current = config.context.clone().or(kubeconfig.current_context);
+
+    let mut table = Table::new();
+    table.set_format(Printer::plain_format());
+    table.set_titles(Row::new(
+        ["CURRENT", "NAME", "CLUSTER", "NAMESPACE"]
+            .iter()
+            .map(|x| Cell::new(x))
+            .collect(),
+    ));
+    for c in &kubeconfig.contexts {
+        let marker = match current.as_ref() == Some(&c.name) {
+            true => "*",
+            false => "",
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(marker),
+            Cell::new(&c.name),
+            Cell::new(&c.context.cluster),
+            Cell::new(c.context.namespace.as_deref().unwrap_or_default()),
+        ]));
+    }
+    table.printstd();
+}
@@ -0,0 +1,168 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+use crate::printer::Format;
+use crate::validator::NamespaceCheck;
+use crate::value;
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How many API calls are made at once by default
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// The defaults that are given in the config file, with the same names as the flags
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+struct File {
+    kubeconfig: Option<String>,
+    context: Option<String>,
+    namespace: Option<String>,
+    output: Option<String>,
+    concurrency: Option<usize>,
+    timeout: Option<String>,
+    request_timeout: Option<String>,
+    no_headers: Option<bool>,
+    no_managed_fields: Option<bool>,
+    namespace_check: Option<String>,
+    no_field_check: Option<bool>,
+}
+
+/// The configuration that is given by the flags, falling back to the config file
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The kubeconfig files to merge instead of the default ones
+    pub kubeconfigs: Vec<PathBuf>,
+    /// The context of the commands that do not take a query, instead of the current one
+    pub context: Option<String>,
+    /// The namespace to query if there is no namespace given in SELECT
+    pub namespace: String,
+    pub format: Format,
+    pub no_headers: bool,
+    pub no_managed_fields: bool,
+    pub color: bool,
+    /// How many API calls are made at once
+    pub concurrency: usize,
+    /// How long the expectations of '--until' are polled for
+    pub timeout: Option<Duration>,
+    /// How long the API server is waited for, instead of the default of the client
+    pub request_timeout: Option<Duration>,
+    /// How the namespaces that are not found in their contexts are reported
+    pub namespace_check: NamespaceCheck,
+    /// Whether the field paths are not validated against the schemas of the API server
+    pub no_field_check: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            kubeconfigs: vec![],
+            context: None,
+            namespace: "default".to_string(),
+            format: Format::default(),
+            no_headers: false,
+            no_managed_fields: false,
+            color: false,
+            concurrency: DEFAULT_CONCURRENCY,
+            timeout: None,
+            request_timeout: None,
+            namespace_check: NamespaceCheck::default(),
+            no_field_check: false,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the path of the config file: i.e. '~/.config/kubesql/config.toml'
+    pub fn path() -> Option<PathBuf> {
+        let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(d) if !d.is_empty() => PathBuf::from(d),
+            _ => dirs::home_dir()?.join(".config"),
+        };
+        Some(dir.join("kubesql").join("config.toml"))
+    }
+
+    /// Reads the config file, if there is any, and overrides it with the given flags
+    pub fn load(matches: &ArgMatches) -> Config {
+        let file = match Config::path().filter(|p| p.exists()) {
+            Some(p) => {
+                let contents = std::fs::read_to_string(&p)
+                    .unwrap_or_else(|err| panic!("Unable to read {}: {}", p.display(), err));
+                toml::from_str::<File>(&contents)
+                    .unwrap_or_else(|err| panic!("Unable to parse {}: {}", p.display(), err))
+            }
+            None => File::default(),
+        };
+
+        let defaults = Config::default();
+        let flag = |name: &str| matches.value_of(name).map(|x| x.to_string());
+
+        Config {
+            // a list of paths like '$KUBECONFIG': i.e. '~/.kube/config:~/.kube/kind'
+            kubeconfigs: flag("kubeconfig")
+                .or(file.kubeconfig)
+                .map(|x| {
+                    std::env::split_paths(&x)
+                        .filter(|p| !p.as_os_str().is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            context: flag("context").or(file.context),
+            namespace: flag("namespace")
+                .or(file.namespace)
+                .unwrap_or(defaults.namespace),
+            format: flag("output")
+                .or(file.output)
+                .map(|x| Format::from_str(&x).unwrap_or_else(|err| panic!("{}", err)))
+                .unwrap_or(defaults.format),
+            no_headers: matches.is_present("no-headers") || file.no_headers.unwrap_or_default(),
+            no_managed_fields: matches.is_present("no-managed-fields")
+                || file.no_managed_fields.unwrap_or_default(),
+            // https://no-color.org
+            color: std::env::var_os("NO_COLOR").is_none()
+                && std::io::IsTerminal::is_terminal(&std::io::stdout()),
+            concurrency: flag("concurrency")
+                .map(|x| x.parse().unwrap())
+                .or(file.concurrency)
+                .filter(|x| *x > 0)
+                .unwrap_or(defaults.concurrency),
+            timeout: flag("timeout")
+                .or(file.timeout)
+                .map(|x| parse_duration(&x).unwrap_or_else(|err| panic!("{}", err))),
+            request_timeout: flag("request-timeout")
+                .or(file.request_timeout)
+                .map(|x| parse_duration(&x).unwrap_or_else(|err| panic!("{}", err))),
+            namespace_check: flag("namespace-check")
+                .or(file.namespace_check)
+                .map(|x| NamespaceCheck::from_str(&x).unwrap_or_else(|err| panic!("{}", err)))
+                .unwrap_or(defaults.namespace_check),
+            no_field_check: matches.is_present("no-field-check")
+                || file.no_field_check.unwrap_or_default(),
+        }
+    }
+}
+
+/// Parses the given positive duration, where numbers without unit are seconds: i.e. '30s'
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    value::parse_duration(input, "second")
+        .filter(|d| *d > chrono::Duration::zero())
+        .and_then(|d| d.to_std().ok())
+        .ok_or_else(|| format!("Invalid duration: '{}', i.e. '30s' or '5m'", input))
+}
@@ -0,0 +1,483 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::planner::{Expression, Projection, Segment, VirtualColumn};
+use crate::value::Value;
+use chrono::Utc;
+use sqlparser::ast::BinaryOperator;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// An object and its metadata that an expression is evaluated against
+#[derive(Debug)]
+pub struct Record<'a> {
+    /// The kubeconfig context that the object comes from
+    pub context: &'a str,
+
+    /// The namespace that the object comes from
+    pub namespace: &'a str,
+
+    /// Kind of the object, i.e. 'pod'
+    pub kind: &'a str,
+
+    /// The object itself, as returned from the API server
+    pub object: &'a serde_json::Value,
+}
+
+/// Evaluates the given expression against the given record
+pub fn evaluate(expr: &Expression, record: &Record) -> Value {
+    eval(expr, record, &[])
+}
+
+/// Evaluates the given expression against the records of a group. Aggregate functions are
+/// evaluated over all records, the rest of the expression against the first record.
+pub fn evaluate_group(expr: &Expression, group: &[&Record]) -> Value {
+    let null = serde_json::Value::Null;
+    let empty = Record {
+        context: "",
+        namespace: "",
+        kind: "",
+        object: &null,
+    };
+
+    eval(expr, group.first().copied().unwrap_or(&empty), group)
+}
+
+fn eval(expr: &Expression, record: &Record, group: &[&Record]) -> Value {
+    match expr {
+        Expression::Column { kind, path } => {
+            if kind.as_deref().is_some_and(|k| k != record.kind) {
+                return Value::Null;
+            }
+            evaluate_path(record.object, path)
+        }
+        Expression::Virtual(v) => evaluate_virtual(v, record),
+        Expression::Literal(v) => v.clone(),
+        Expression::Function { function, args } => function.call(
+            &args
+                .iter()
+                .map(|x| eval(x, record, group))
+                .collect::<Vec<Value>>(),
+        ),
+        Expression::Aggregate {
+            aggregate,
+            arg,
+            distinct,
+        } => {
+            let mut values = group
+                .iter()
+                .map(|r| match arg {
+                    Some(a) => eval(a, r, &[]),
+                    None => Value::Bool(true),
+                })
+                .collect::<Vec<Value>>();
+            if *distinct {
+                // each element of a list is a value on its own: i.e. the images of the pods
+                values = values.into_iter().flat_map(Value::into_elements).collect();
+                let mut seen = HashSet::new();
+                values.retain(|x| seen.insert(x.key()));
+            }
+            aggregate.apply(values)
+        }
+        Expression::BinaryOp { left, op, right } => {
+            evaluate_binary_op(left, op, right, record, group)
+        }
+        Expression::Any { expr, op, list } => {
+            let l = eval(expr, record, group);
+            let elements = match eval(list, record, group) {
+                Value::List(v) => v,
+                Value::Null => vec![],
+                v => vec![v],
+            };
+            Value::Bool(
+                elements
+                    .into_iter()
+                    .any(|x| compare(l.clone(), op, x).is_true()),
+            )
+        }
+        Expression::Not(e) => match eval(e, record, group) {
+            Value::Bool(b) => Value::Bool(!b),
+            _ => Value::Null,
+        },
+        Expression::IsNull { expr, negated } => {
+            Value::Bool(eval(expr, record, group).is_null() != *negated)
+        }
+        Expression::Parameter(_) => panic!("Unbound parameter: {}", expr),
+    }
+}
+
+fn evaluate_virtual(v: &VirtualColumn, record: &Record) -> Value {
+    let non_empty = |s: &str| match s.is_empty() {
+        true => Value::Null,
+        false => Value::String(s.to_string()),
+    };
+
+    match v {
+        VirtualColumn::Context => non_empty(record.context),
+        VirtualColumn::Namespace => non_empty(record.namespace),
+        VirtualColumn::Kind => non_empty(record.kind),
+        VirtualColumn::ApiVersion => record
+            .object
+            .get("apiVersion")
+            .map_or(Value::Null, Value::from),
+        VirtualColumn::Age => record
+            .object
+            .pointer("/metadata/creationTimestamp")
+            .map(Value::from)
+            .and_then(|x| x.as_timestamp())
+            .map_or(Value::Null, |t| Value::Duration(Utc::now() - t)),
+    }
+}
+
+/// Evaluates the given projections against the record. UNNEST projections are expanded to one
+/// row per element, where the other columns are repeated and shorter lists are padded with NULLs.
+pub fn project(projections: &[Projection], record: &Record) -> Vec<Vec<Value>> {
+    let columns = projections
+        .iter()
+        .map(|p| match (p.unnest, evaluate(&p.expr, record)) {
+            (true, Value::List(v)) => (true, v),
+            (true, Value::Null) => (true, vec![]),
+            (unnest, v) => (unnest, vec![v]),
+        })
+        .collect::<Vec<(bool, Vec<Value>)>>();
+
+    let rows = match columns.iter().any(|(unnest, _)| *unnest) {
+        true => columns
+            .iter()
+            .filter(|(unnest, _)| *unnest)
+            .map(|(_, v)| v.len())
+            .max()
+            .unwrap_or(0),
+        false => 1,
+    };
+
+    (0..rows)
+        .map(|i| {
+            columns
+                .iter()
+                .map(|(unnest, v)| match unnest {
+                    true => v.get(i).cloned().unwrap_or(Value::Null),
+                    false => v[0].clone(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Walks the given field path of the object, i.e. ['spec', 'containers', '[*]', 'image'].
+/// Arrays on the way are expanded and the matched values are returned as a list.
+fn evaluate_path(object: &serde_json::Value, path: &[Segment]) -> Value {
+    let mut values = vec![];
+    if walk(object, path, &mut values) {
+        Value::List(values.into_iter().map(Value::from).collect())
+    } else {
+        values.first().map_or(Value::Null, |x| Value::from(*x))
+    }
+}
+
+/// Returns the JSON values at the given path, each array element separately if any array is
+/// expanded on the way
+pub fn select<'a>(object: &'a serde_json::Value, path: &[Segment]) -> Vec<&'a serde_json::Value> {
+    let mut values = vec![];
+    walk(object, path, &mut values);
+    values
+}
+
+/// Collects the values at the given path, returns whether any array is expanded on the way
+fn walk<'a>(
+    object: &'a serde_json::Value,
+    path: &[Segment],
+    values: &mut Vec<&'a serde_json::Value>,
+) -> bool {
+    let (segment, rest) = match path.split_first() {
+        Some(x) => x,
+        None => {
+            values.push(object);
+            return false;
+        }
+    };
+
+    match (segment, object) {
+        // 'spec.containers.image' is a shorthand for 'spec.containers[*].image'
+        (Segment::Field(_), serde_json::Value::Array(a)) => {
+            a.iter().for_each(|x| {
+                walk(x, path, values);
+            });
+            true
+        }
+        (Segment::Field(f), serde_json::Value::Object(o)) => match o.get(f) {
+            Some(x) => walk(x, rest, values),
+            None => false,
+        },
+        (Segment::Index(i), serde_json::Value::Array(a)) => {
+            let i = if *i < 0 { a.len() as i64 + i } else { *i };
+            match a.get(i as usize) {
+                Some(x) if i >= 0 => walk(x, rest, values),
+                _ => false,
+            }
+        }
+        (Segment::Wildcard, serde_json::Value::Array(a)) => {
+            a.iter().for_each(|x| {
+                walk(x, rest, values);
+            });
+            true
+        }
+        (Segment::Wildcard, serde_json::Value::Object(o)) => {
+            o.values().for_each(|x| {
+                walk(x, rest, values);
+            });
+            true
+        }
+        _ => false,
+    }
+}
+
+fn evaluate_binary_op(
+    left: &Expression,
+    op: &BinaryOperator,
+    right: &Expression,
+    record: &Record,
+    group: &[&Record],
+) -> Value {
+    match op {
+        BinaryOperator::And => {
+            Value::Bool(eval(left, record, group).is_true() && eval(right, record, group).is_true())
+        }
+        BinaryOperator::Or => {
+            Value::Bool(eval(left, record, group).is_true() || eval(right, record, group).is_true())
+        }
+        _ => compare(eval(left, record, group), op, eval(right, record, group)),
+    }
+}
+
+/// Compares the values with the given operator. A list is compared element by element with a
+/// non-list value, and matches if any of its elements does.
+fn compare(l: Value, op: &BinaryOperator, r: Value) -> Value {
+    match (l, r) {
+        (Value::Null, _) | (_, Value::Null) => Value::Null,
+        // 'pod.status.containerStatuses.restartCount > 3' matches if any of the containers does
+        (Value::List(v), r) if !matches!(r, Value::List(_)) && is_comparison(op) => {
+            Value::Bool(v.into_iter().any(|x| compare(x, op, r.clone()).is_true()))
+        }
+        (l, Value::List(v)) if !matches!(l, Value::List(_)) && is_comparison(op) => {
+            Value::Bool(v.into_iter().any(|x| compare(l.clone(), op, x).is_true()))
+        }
+        (l, r) => match op {
+            BinaryOperator::Eq => Value::Bool(l.equals(&r)),
+            BinaryOperator::NotEq => Value::Bool(!l.equals(&r)),
+            BinaryOperator::Gt
+            | BinaryOperator::Lt
+            | BinaryOperator::GtEq
+            | BinaryOperator::LtEq => l.compare(&r).map_or(Value::Null, |o| {
+                Value::Bool(match op {
+                    BinaryOperator::Gt => o == Ordering::Greater,
+                    BinaryOperator::Lt => o == Ordering::Less,
+                    BinaryOperator::GtEq => o != Ordering::Less,
+                    _ => o != Ordering::Greater,
+                })
+            }),
+            BinaryOperator::StringConcat => Value::String(format!("{}{}", l, r)),
+            _ => arithmetic(l, op, r),
+        },
+    }
+}
+
+/// Returns whether the operator compares its operands: i.e. '=' or '>'
+pub fn is_comparison(op: &BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::Eq
+            | BinaryOperator::NotEq
+            | BinaryOperator::Gt
+            | BinaryOperator::Lt
+            | BinaryOperator::GtEq
+            | BinaryOperator::LtEq
+    )
+}
+
+/// Applies the arithmetic operator on numbers, timestamps and durations
+fn arithmetic(l: Value, op: &BinaryOperator, r: Value) -> Value {
+    match (&l, op, &r) {
+        (Value::Integer(a), _, Value::Integer(b)) => match op {
+            BinaryOperator::Plus => a.checked_add(*b),
+            BinaryOperator::Minus => a.checked_sub(*b),
+            BinaryOperator::Multiply => a.checked_mul(*b),
+            BinaryOperator::Divide => a.checked_div(*b),
+            BinaryOperator::Modulus => a.checked_rem(*b),
+            _ => panic!("Unsupported operator: {}", op),
+        }
+        .map_or(Value::Null, Value::Integer),
+        (Value::Timestamp(t), BinaryOperator::Plus, _) => r
+            .as_duration()
+            .map_or(Value::Null, |d| Value::Timestamp(*t + d)),
+        (Value::Timestamp(t), BinaryOperator::Minus, Value::Timestamp(_)) => r
+            .as_timestamp()
+            .map_or(Value::Null, |x| Value::Duration(*t - x)),
+        // a string is either a timestamp or an interval: i.e. NOW() - '1 day'
+        (Value::Timestamp(t), BinaryOperator::Minus, Value::String(_)) => {
+            match (r.as_timestamp(), r.as_duration()) {
+                (Some(x), _) => Value::Duration(*t - x),
+                (None, Some(d)) => Value::Timestamp(*t - d),
+                _ => Value::Null,
+            }
+        }
+        (Value::Timestamp(t), BinaryOperator::Minus, _) => r
+            .as_duration()
+            .map_or(Value::Null, |d| Value::Timestamp(*t - d)),
+        (Value::Duration(_), BinaryOperator::Plus, _)
+        | (Value::Duration(_), BinaryOperator::Minus, _) => {
+            match (l.as_duration(), r.as_duration()) {
+                (Some(a), Some(b)) if *op == BinaryOperator::Plus => Value::Duration(a + b),
+                (Some(a), Some(b)) => Value::Duration(a - b),
+                _ => Value::Null,
+            }
+        }
+        _ => match (l.as_f64(), r.as_f64()) {
+            (Some(a), Some(b)) => Value::Float(match op {
+                BinaryOperator::Plus => a + b,
+                BinaryOperator::Minus => a - b,
+                BinaryOperator::Multiply => a * b,
+                BinaryOperator::Divide => a / b,
+                BinaryOperator::Modulus => a % b,
+                _ => panic!("Unsupported operator: {}", op),
+            }),
+            _ => Value::Null,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+    use chrono::Duration;
+
+    /// Evaluates the given standalone expression against the given pod
+    fn eval(sql: &str, object: &serde_json::Value) -> Value {
+        evaluate(
+            &parser::parse_condition(sql, &Default::default()),
+            &Record {
+                context: "minikube",
+                namespace: "default",
+                kind: "pod",
+                object,
+            },
+        )
+    }
+
+    #[test]
+    fn strings_are_subtracted_from_timestamps_as_intervals_or_timestamps() {
+        let null = serde_json::Value::Null;
+        let day_ago = eval("NOW() - '1 day'", &null).as_timestamp().unwrap();
+        let elapsed = Utc::now() - day_ago;
+        assert!(elapsed >= Duration::days(1) && elapsed < Duration::days(1) + Duration::minutes(1));
+
+        assert_eq!(
+            eval("NOW() - '2021-01-01T00:00:00Z' > INTERVAL '1' DAY", &null),
+            Value::Bool(true)
+        );
+        assert_eq!(eval("NOW() - 'yesterday'", &null), Value::Null);
+    }
+
+    fn pod() -> serde_json::Value {
+        serde_json::json!({
+            "metadata": { "name": "web" },
+            "spec": {
+                "containers": [
+                    { "name": "app", "image": "nginx", "ports": [{ "containerPort": 80 }] },
+                    { "name": "sidecar", "image": "envoy" },
+                ],
+            },
+            "status": { "containerStatuses": [{ "restartCount": 0 }, { "restartCount": 5 }] },
+        })
+    }
+
+    #[test]
+    fn indexes_are_counted_from_the_start_or_the_end() {
+        let pod = pod();
+        assert_eq!(
+            eval("pod.spec.containers[0].name", &pod),
+            Value::String("app".to_string())
+        );
+        assert_eq!(
+            eval("pod.spec.containers[-1].name", &pod),
+            Value::String("sidecar".to_string())
+        );
+        assert_eq!(eval("pod.spec.containers[2].name", &pod), Value::Null);
+        assert_eq!(eval("pod.spec.containers[-3].name", &pod), Value::Null);
+        assert_eq!(eval("pod.metadata.name[0]", &pod), Value::Null);
+    }
+
+    #[test]
+    fn arrays_on_the_path_are_expanded_to_lists() {
+        let pod = pod();
+        let images = Value::List(vec![
+            Value::String("nginx".to_string()),
+            Value::String("envoy".to_string()),
+        ]);
+        assert_eq!(eval("pod.spec.containers[*].image", &pod), images);
+        assert_eq!(eval("pod.spec.containers.image", &pod), images);
+        assert_eq!(
+            eval("pod.spec.containers.ports.containerPort", &pod),
+            Value::List(vec![Value::Integer(80)])
+        );
+    }
+
+    #[test]
+    fn any_matches_if_an_element_matches() {
+        let pod = pod();
+        assert!(eval("'envoy' = ANY(pod.spec.containers[*].image)", &pod).is_true());
+        assert!(!eval("'redis' = ANY(pod.spec.containers[*].image)", &pod).is_true());
+        assert!(!eval("'redis' = ANY(pod.spec.volumes[*].name)", &pod).is_true());
+    }
+
+    #[test]
+    fn comparisons_with_lists_match_if_an_element_matches() {
+        let pod = pod();
+        assert!(eval("pod.status.containerStatuses.restartCount > 3", &pod).is_true());
+        assert!(!eval("pod.status.containerStatuses.restartCount > 5", &pod).is_true());
+        assert!(eval("pod.spec.containers.image = 'nginx'", &pod).is_true());
+    }
+
+    #[test]
+    fn unnest_projects_a_row_per_element() {
+        let pod = pod();
+        let q = parser::parse_sql(
+            "SELECT default, pod.metadata.name, UNNEST(pod.spec.containers[*].image), UNNEST(pod.spec.containers.ports.containerPort) FROM minikube WHERE pod.metadata.name = 'web'",
+            &Default::default(),
+        );
+        let rows = project(
+            &q.projections,
+            &Record {
+                context: "minikube",
+                namespace: "default",
+                kind: "pod",
+                object: &pod,
+            },
+        );
+        let text = rows
+            .iter()
+            .map(|r| r.iter().map(|x| x.to_string()).collect::<Vec<String>>())
+            .collect::<Vec<Vec<String>>>();
+        assert_eq!(
+            text,
+            vec![vec!["web", "nginx", "80"], vec!["web", "envoy", "<none>"]]
+        );
+    }
+}
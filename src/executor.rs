@@ -0,0 +1,677 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::api_builder::ApiBuilder;
+use crate::config::Config;
+use crate::evaluator;
+use crate::evaluator::Record;
+use crate::parser;
+use crate::parser::{ApiQueries, ResourceType};
+use crate::planner::{Expression, Parameters};
+use crate::printer::{Format, Printer};
+use crate::result;
+use crate::result::Entry;
+use crate::schema::Schema;
+use crate::summary;
+use crate::summary::{Call, Summary};
+use crate::validator;
+use crate::validator::NamespaceCheck;
+use crate::value::Value;
+use crate::watcher;
+use crate::watcher::Change;
+use anyhow::{bail, Result};
+use futures::StreamExt;
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::core::v1::{Namespace, Pod};
+use kube::api::{ListParams, ObjectList, Resource};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlparser::ast::BinaryOperator;
+use std::collections::{BTreeMap, HashMap};
+use std::io::IsTerminal;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Options of how the queries are run
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Whether the counts and timings are printed after the results
+    pub summary: bool,
+    /// The condition that the objects satisfying the query are expected to meet: i.e. `COUNT(*) = 0`
+    pub until: Option<Expression>,
+    /// Whether the query is expected to return no rows
+    pub fail_if_rows: bool,
+    /// How often the query is run again until the expectations are met, within the timeout
+    pub interval: Option<Duration>,
+    /// The values of the placeholders of the queries
+    pub parameters: Parameters,
+}
+
+/// Runs the queries, keeping the clients of the contexts and namespaces warm across them
+pub struct Executor {
+    config: Config,
+    options: Options,
+    apis: Vec<ApiBuilder>,
+    /// The schemas of the contexts that the field paths are validated against
+    schemas: HashMap<String, Schema>,
+}
+
+impl Executor {
+    pub fn new(config: Config, options: Options) -> Executor {
+        Executor {
+            config,
+            options,
+            apis: vec![],
+            schemas: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the APIs that have been built so far
+    pub fn apis(&self) -> &[ApiBuilder] {
+        &self.apis
+    }
+
+    /// Returns the index of the API of the given context and namespace, building it only once.
+    /// The current context of the kubeconfig is used if there is no context given.
+    pub async fn api(&mut self, context: Option<&str>, namespace: &str) -> Result<usize> {
+        let position = |apis: &[ApiBuilder], context: &str| {
+            apis.iter()
+                .position(|x| x.get_context() == context && x.get_namespace() == namespace)
+        };
+
+        if let Some(i) = context.and_then(|c| position(&self.apis, c)) {
+            return Ok(i);
+        }
+
+        let mut api = ApiBuilder::builder()
+            .config(&self.config)
+            .namespace(namespace.to_string());
+        if let Some(c) = context {
+            api = api.context(c.to_string());
+        }
+        let api = api.build().await?;
+
+        // the current context is only known once it is built
+        if let Some(i) = position(&self.apis, api.get_context()) {
+            return Ok(i);
+        }
+
+        self.apis.push(api);
+        Ok(self.apis.len() - 1)
+    }
+
+    /// Parses the given query, querying the configured namespace if there is no namespace given,
+    /// and the configured context if there is no FROM
+    pub fn parse(&self, sql: &str) -> ApiQueries {
+        let mut api_queries = parser::parse_sql(sql, &self.options.parameters);
+        if api_queries.namespaces.is_empty() {
+            api_queries.namespaces.push(self.config.namespace.clone());
+        }
+        if api_queries.contexts.is_empty() {
+            api_queries.contexts.extend(self.config.context.clone());
+        }
+        api_queries
+    }
+
+    /// Validates the contexts, namespaces and field paths of the given queries and returns the
+    /// indexes of their APIs. The current context is queried if there is none, which is added to the queries
+    /// afterwards.
+    async fn connect(
+        &mut self,
+        api_queries: &mut ApiQueries,
+        summary: &mut Summary,
+    ) -> Result<Vec<usize>> {
+        let kubeconfig = parser::parse_kubeconfig(&self.config.kubeconfigs);
+
+        validator::validate_contexts(kubeconfig, &api_queries.contexts);
+
+        let mut apis: Vec<usize> = Vec::new();
+        if api_queries.contexts.is_empty() {
+            for ns in &api_queries.namespaces {
+                apis.push(self.api(None, ns).await?);
+            }
+            let current = self.apis[apis[0]].get_context().clone();
+            api_queries.contexts.push(current);
+        } else {
+            for ctx in &api_queries.contexts {
+                for ns in &api_queries.namespaces {
+                    apis.push(self.api(Some(ctx), ns).await?);
+                }
+            }
+        }
+
+        // before any object is listed, since a typo would only look like there are no objects
+        if self.config.namespace_check != NamespaceCheck::Skip {
+            let existing = self.namespaces(&apis, summary).await;
+            validator::validate_namespaces(
+                &existing,
+                &api_queries.namespaces,
+                self.config.namespace_check,
+            );
+        }
+
+        if !self.config.no_field_check {
+            self.fetch_schemas(&apis, summary).await;
+            for ctx in &api_queries.contexts {
+                if let Some(schema) = self.schemas.get(ctx) {
+                    validator::validate_fields(schema, api_queries);
+                }
+            }
+        }
+        Ok(apis)
+    }
+
+    /// Fetches the schemas of the contexts of the given APIs, once per context. The contexts whose
+    /// schemas could not be fetched are not validated, with a warning.
+    async fn fetch_schemas(&mut self, apis: &[usize], summary: &mut Summary) {
+        let mut fetched: Vec<String> = Vec::new();
+        for i in apis {
+            let context = self.apis[*i].get_context().clone();
+            if self.schemas.contains_key(&context) || fetched.contains(&context) {
+                continue;
+            }
+            fetched.push(context.clone());
+            let started = Instant::now();
+            match Schema::fetch(self.apis[*i].get_client()).await {
+                Ok((s, bytes)) => {
+                    summary.record(Call {
+                        context: context.clone(),
+                        namespace: String::new(),
+                        kind: "openapi".to_string(),
+                        listed: None,
+                        matched: None,
+                        bytes,
+                        started,
+                        elapsed: started.elapsed(),
+                    });
+                    self.schemas.insert(context, s);
+                }
+                Err(e) => eprintln!(
+                    "Warning: Could not fetch the schemas of {:?} to validate the fields: {}",
+                    context, e
+                ),
+            }
+        }
+    }
+
+    /// Lists the namespaces of the contexts of the given APIs. The contexts whose namespaces
+    /// could not be listed, i.e. due to RBAC, are left out with a warning.
+    async fn namespaces(
+        &self,
+        apis: &[usize],
+        summary: &mut Summary,
+    ) -> Vec<(String, Vec<String>)> {
+        let mut clients: Vec<&ApiBuilder> = Vec::new();
+        for api in apis.iter().map(|i| &self.apis[*i]) {
+            if clients.iter().all(|x| x.get_context() != api.get_context()) {
+                clients.push(api);
+            }
+        }
+
+        let mut lists = futures::stream::iter(clients)
+            .map(|api| async move {
+                let started = Instant::now();
+                let list = list_namespaces(api).await;
+                (api.get_context(), list, started, started.elapsed())
+            })
+            .buffered(self.config.concurrency);
+
+        let mut existing = Vec::new();
+        while let Some((context, list, started, elapsed)) = lists.next().await {
+            match list {
+                Ok((names, bytes)) => {
+                    summary.record(Call {
+                        context: context.clone(),
+                        namespace: String::new(),
+                        kind: "namespace".to_string(),
+                        listed: Some(names.len()),
+                        matched: None,
+                        bytes,
+                        started,
+                        elapsed,
+                    });
+                    existing.push((context.clone(), names));
+                }
+                Err(e) => eprintln!(
+                    "Warning: Could not list the namespaces of {:?} to validate them: {}",
+                    context, e
+                ),
+            }
+        }
+        existing
+    }
+
+    /// Runs the given query until its results meet the expectations, every interval if given.
+    /// Fails if they are not met in time, or at all if the query is run only once.
+    pub async fn run(&mut self, sql: &str) -> Result<()> {
+        let started = Instant::now();
+
+        loop {
+            let reason = match self.execute(sql).await? {
+                Some(r) => r,
+                None => return Ok(()),
+            };
+
+            let mut interval = match self.options.interval {
+                Some(i) => i,
+                None => bail!(reason),
+            };
+            if let Some(timeout) = self.config.timeout {
+                let elapsed = started.elapsed();
+                if elapsed >= timeout {
+                    bail!("{}, after {}", reason, summary::format_elapsed(elapsed));
+                }
+                // the query is run one last time once the timeout is reached
+                interval = interval.min(timeout - elapsed);
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Runs the given query and prints its results. Returns the reason why the results do not
+    /// meet the expectations, if any.
+    pub async fn execute(&mut self, sql: &str) -> Result<Option<String>> {
+        let mut summary = Summary::new();
+
+        let mut api_queries = self.parse(sql);
+
+        let apis = self.connect(&mut api_queries, &mut summary).await?;
+
+        let projections = match api_queries.projections.is_empty() {
+            true => result::default_projections(),
+            false => api_queries.projections.clone(),
+        };
+
+        // the rows are printed as soon as they are listed, if nothing needs to be known about the others
+        let stream = self.config.format == Format::Ndjson
+            && result::streamable(
+                &projections,
+                &api_queries.group_by,
+                &api_queries.order_by,
+                api_queries.distinct,
+            );
+
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut rows = 0;
+        // the kinds that are joined with AND, but have no objects satisfying their conditions
+        let mut missing: Vec<String> = Vec::new();
+
+        //1. Query { key: None, kind: "pod", predicate: pod.status.phase = 'Running' }
+        //2. Query { key: Some(And), kind: "deployment", predicate: deployment.metadata.name = 'my-awesome-deployment' }
+        for q in api_queries.queries.clone() {
+            // a.k.a '--field-selector': https://v1-18.docs.kubernetes.io/docs/concepts/overview/working-with-objects/field-selectors/
+            // the predicate is evaluated on our side as well, since not every field is supported
+            let list_params = match q.field_selector() {
+                Some(f) => ListParams::default().fields(&f),
+                None => ListParams::default(),
+            };
+            let mut found = false;
+
+            // the APIs are listed concurrently, but their objects are handled in order
+            let mut lists = futures::stream::iter(apis.iter().map(|i| &self.apis[*i]))
+                .map(|api| {
+                    let (kind, list_params) = (&q.kind, &list_params);
+                    async move {
+                        let started = Instant::now();
+                        let objects = list(api, kind, list_params).await;
+                        (api, objects, started, started.elapsed())
+                    }
+                })
+                .buffered(self.config.concurrency);
+
+            while let Some((api, objects, started, elapsed)) = lists.next().await {
+                let (objects, bytes) = objects?;
+                let listed = objects.len();
+
+                let matched = objects
+                    .into_iter()
+                    .map(|object| Entry {
+                        context: api.get_context().clone(),
+                        namespace: api.get_namespace().clone(),
+                        kind: q.kind.clone(),
+                        object: Rc::new(object),
+                    })
+                    .filter(|e| q.matches(&e.record()))
+                    .collect::<Vec<Entry>>();
+                found |= !matched.is_empty();
+                summary.record(Call {
+                    context: api.get_context().clone(),
+                    namespace: api.get_namespace().clone(),
+                    kind: q.kind.clone(),
+                    listed: Some(listed),
+                    matched: Some(matched.len()),
+                    bytes,
+                    started,
+                    elapsed,
+                });
+                if stream {
+                    let built = result::build(&matched, &projections, &[], &[], false);
+                    rows += built.len();
+                    Printer::builder()
+                        .config(&self.config)
+                        .projections(&projections)
+                        .rows(built)
+                        .print();
+                }
+                // the entries are kept for the condition only, if the rows are already printed
+                if !stream || self.options.until.is_some() {
+                    entries.extend(matched);
+                }
+            }
+
+            // we will decide according to given operator, in case if resource not found or empty
+            if !found && q.key == Some(BinaryOperator::And) {
+                missing.push(format!(
+                    "No resource found: '{}' WHERE {}",
+                    q.kind, q.predicate
+                ));
+            }
+        }
+
+        if !stream {
+            let built = result::build(
+                &entries,
+                &projections,
+                &api_queries.group_by,
+                &api_queries.order_by,
+                api_queries.distinct,
+            );
+            rows = built.len();
+
+            let printer = Printer::builder()
+                .config(&self.config)
+                .contexts(&api_queries.contexts)
+                .namespaces(&api_queries.namespaces)
+                .queries(api_queries.queries.as_slice())
+                .projections(api_queries.projections.as_slice())
+                .rows(built);
+
+            printer.print();
+        }
+
+        if self.options.summary {
+            summary.print();
+        }
+
+        Ok(self.check(&missing, &entries, rows))
+    }
+
+    /// Returns the reason why the given results do not meet the expectations, if any. The kinds
+    /// that are joined with AND but not found are not met either.
+    fn check(&self, missing: &[String], entries: &[Entry], rows: usize) -> Option<String> {
+        if !missing.is_empty() {
+            return Some(missing.join("; "));
+        }
+
+        if self.options.fail_if_rows && rows > 0 {
+            return Some(format!("The query returned {} row(s)", rows));
+        }
+
+        if let Some(until) = &self.options.until {
+            let records = entries.iter().map(|x| x.record()).collect::<Vec<Record>>();
+            let group = records.iter().collect::<Vec<&Record>>();
+            if evaluator::evaluate_group(until, &group) != Value::Bool(true) {
+                // i.e. 'COUNT(*) = 0 (COUNT(*) is 3)', which tells how far the results are
+                let actual = match until {
+                    Expression::BinaryOp { left, .. } => {
+                        format!(" ({} is {})", left, evaluator::evaluate_group(left, &group))
+                    }
+                    _ => String::new(),
+                };
+                return Some(format!("The condition is not met: {}{}", until, actual));
+            }
+        }
+
+        None
+    }
+}
+
+impl Executor {
+    /// Runs the given query and keeps its results up to date as the objects change, until an
+    /// error occurs. The results are printed again on every change, unless they are printed as
+    /// NDJSON and each row stands on its own, where the added, modified and deleted rows are
+    /// printed as events instead.
+    pub async fn watch(&mut self, sql: &str) -> Result<()> {
+        let mut api_queries = self.parse(sql);
+
+        let apis = self.connect(&mut api_queries, &mut Summary::new()).await?;
+
+        let projections = match api_queries.projections.is_empty() {
+            true => result::default_projections(),
+            false => api_queries.projections.clone(),
+        };
+
+        let events = self.config.format == Format::Ndjson
+            && result::streamable(
+                &projections,
+                &api_queries.group_by,
+                &api_queries.order_by,
+                api_queries.distinct,
+            );
+
+        // a source per query and API, each watched on its own
+        let mut sources = Vec::new();
+        let mut streams = Vec::new();
+        for q in &api_queries.queries {
+            let list_params = match q.field_selector() {
+                Some(f) => ListParams::default().fields(&f),
+                None => ListParams::default(),
+            };
+
+            for api in apis.iter().map(|i| &self.apis[*i]) {
+                let changes = match parser::ResourceType::from_str(&q.kind.to_lowercase())
+                    .unwrap_or_else(|_| panic!("Unexpected ResourceType for {}", q.kind))
+                {
+                    ResourceType::Deployment => {
+                        watcher::watch(api.get_deployment().clone(), list_params.clone())
+                    }
+                    ResourceType::Pod => watcher::watch(api.get_pod().clone(), list_params.clone()),
+                };
+
+                let source = sources.len();
+                sources.push((q, api));
+                streams.push(changes.map(move |c| (source, c)));
+            }
+        }
+
+        let entry = |source: usize, object: Rc<serde_json::Value>| {
+            let (q, api) = sources[source];
+            Entry {
+                context: api.get_context().clone(),
+                namespace: api.get_namespace().clone(),
+                kind: q.kind.clone(),
+                object,
+            }
+        };
+
+        // the objects that satisfy the query per source, by their names
+        let mut matched: Vec<BTreeMap<String, Rc<serde_json::Value>>> =
+            vec![BTreeMap::new(); sources.len()];
+        let mut listed = vec![false; sources.len()];
+        let mut rendered = false;
+
+        let mut changes = futures::stream::select_all(streams);
+        while let Some((source, change)) = changes.next().await {
+            let (q, _) = sources[source];
+            let mut changed: Vec<(&str, Entry)> = Vec::new();
+
+            match change? {
+                Change::Listed(objects) => {
+                    let mut objects = objects
+                        .into_iter()
+                        .map(|x| entry(source, Rc::new(x)))
+                        .filter(|e| q.matches(&e.record()))
+                        .map(|e| (name(&e.object), e.object))
+                        .collect::<BTreeMap<String, Rc<serde_json::Value>>>();
+                    std::mem::swap(&mut matched[source], &mut objects);
+
+                    for (k, o) in objects.iter() {
+                        if !matched[source].contains_key(k) {
+                            changed.push(("DELETED", entry(source, o.clone())));
+                        }
+                    }
+                    for (k, o) in matched[source].iter() {
+                        match objects.get(k) {
+                            None => changed.push(("ADDED", entry(source, o.clone()))),
+                            Some(x) if x != o => {
+                                changed.push(("MODIFIED", entry(source, o.clone())))
+                            }
+                            _ => {}
+                        }
+                    }
+                    listed[source] = true;
+                }
+                Change::Applied(o) => {
+                    let e = entry(source, Rc::new(o));
+                    let k = name(&e.object);
+                    if q.matches(&e.record()) {
+                        let event = match matched[source].contains_key(&k) {
+                            true => "MODIFIED",
+                            false => "ADDED",
+                        };
+                        matched[source].insert(k, e.object.clone());
+                        changed.push((event, e));
+                    } else if let Some(o) = matched[source].remove(&k) {
+                        // it does not satisfy the query anymore
+                        changed.push(("DELETED", entry(source, o)));
+                    }
+                }
+                Change::Deleted(o) => {
+                    if let Some(o) = matched[source].remove(&name(&o)) {
+                        changed.push(("DELETED", entry(source, o)));
+                    }
+                }
+            }
+
+            if events {
+                for (event, e) in changed {
+                    Printer::builder()
+                        .projections(&projections)
+                        .rows(result::build(&[e], &projections, &[], &[], false))
+                        .print_events(event);
+                }
+                continue;
+            }
+
+            // the results are printed once every source is listed, and on every change afterwards
+            if !listed.iter().all(|x| *x) || (rendered && changed.is_empty()) {
+                continue;
+            }
+            rendered = true;
+
+            let entries = matched
+                .iter()
+                .enumerate()
+                .flat_map(|(s, objects)| objects.values().map(move |o| (s, o.clone())))
+                .map(|(s, o)| entry(s, o))
+                .collect::<Vec<Entry>>();
+
+            let rows = result::build(
+                &entries,
+                &projections,
+                &api_queries.group_by,
+                &api_queries.order_by,
+                api_queries.distinct,
+            );
+
+            if std::io::stdout().is_terminal() {
+                // clears the screen and moves the cursor to the top, like watch(1)
+                print!("\x1B[2J\x1B[H");
+            }
+
+            Printer::builder()
+                .config(&self.config)
+                .contexts(&api_queries.contexts)
+                .namespaces(&api_queries.namespaces)
+                .queries(api_queries.queries.as_slice())
+                .projections(api_queries.projections.as_slice())
+                .rows(rows)
+                .print();
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the name of the given object, which is unique within its kind and namespace
+fn name(object: &serde_json::Value) -> String {
+    object["metadata"]["name"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Lists the objects of the given kind from the given API
+async fn list(
+    api: &ApiBuilder,
+    kind: &str,
+    list_params: &ListParams,
+) -> Result<(Vec<serde_json::Value>, usize)> {
+    match parser::ResourceType::from_str(&kind.to_lowercase())
+        .unwrap_or_else(|_| panic!("Unexpected ResourceType for {}", kind))
+    {
+        ResourceType::Deployment => list_as::<Deployment>(api, list_params).await,
+        ResourceType::Pod => list_as::<Pod>(api, list_params).await,
+    }
+}
+
+/// Lists the objects of the given type, along with the size of the response body. The request is
+/// made by hand, since `Api::list` does not tell the size.
+async fn list_as<K>(
+    api: &ApiBuilder,
+    list_params: &ListParams,
+) -> Result<(Vec<serde_json::Value>, usize)>
+where
+    K: Resource<DynamicType = ()> + DeserializeOwned + Serialize + Clone,
+{
+    let url = K::url_path(&(), Some(api.get_namespace()));
+    let request = kube::api::Request::new(url).list(list_params)?;
+    let body = api.get_client().request_text(request).await?;
+    let objects: ObjectList<K> = serde_json::from_str(&body)?;
+    Ok((to_values(objects), body.len()))
+}
+
+/// Lists the names of the namespaces of the given API, along with the size of the response body
+async fn list_namespaces(api: &ApiBuilder) -> Result<(Vec<String>, usize)> {
+    let url = Namespace::url_path(&(), None);
+    let request = kube::api::Request::new(url).list(&ListParams::default())?;
+    let body = api.get_client().request_text(request).await?;
+    let namespaces: ObjectList<Namespace> = serde_json::from_str(&body)?;
+    let names = namespaces
+        .items
+        .into_iter()
+        .filter_map(|x| x.metadata.name)
+        .collect();
+    Ok((names, body.len()))
+}
+
+/// Converts the listed objects to JSON in order to evaluate expressions against them
+fn to_values<K: Serialize + Clone>(objects: ObjectList<K>) -> Vec<serde_json::Value> {
+    objects
+        .items
+        .iter()
+        .map(|x| serde_json::to_value(x).unwrap())
+        .collect()
+}
@@ -0,0 +1,395 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::value::{Quantity, Value};
+use chrono::Utc;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Signature of a scalar function implementation
+type ScalarFn = fn(&[Value]) -> Value;
+
+/// A scalar function that can be called from SELECT and WHERE statements
+pub struct Function {
+    /// Upper-case name of the function, i.e. 'LOWER'
+    pub name: &'static str,
+
+    /// Minimum number of arguments
+    pub min_args: usize,
+
+    /// Maximum number of arguments, `None` for variadic functions
+    pub max_args: Option<usize>,
+
+    call: ScalarFn,
+}
+
+/// The registry of all supported scalar functions
+static FUNCTIONS: [Function; 10] = [
+    Function {
+        name: "LOWER",
+        min_args: 1,
+        max_args: Some(1),
+        call: lower,
+    },
+    Function {
+        name: "UPPER",
+        min_args: 1,
+        max_args: Some(1),
+        call: upper,
+    },
+    Function {
+        name: "SPLIT_PART",
+        min_args: 3,
+        max_args: Some(3),
+        call: split_part,
+    },
+    Function {
+        name: "SUBSTRING",
+        min_args: 1,
+        max_args: Some(3),
+        call: substring,
+    },
+    Function {
+        name: "CONCAT",
+        min_args: 1,
+        max_args: None,
+        call: concat,
+    },
+    Function {
+        name: "COALESCE",
+        min_args: 1,
+        max_args: None,
+        call: coalesce,
+    },
+    Function {
+        name: "AGE",
+        min_args: 1,
+        max_args: Some(1),
+        call: age,
+    },
+    Function {
+        name: "NOW",
+        min_args: 0,
+        max_args: Some(0),
+        call: now,
+    },
+    Function {
+        name: "PARSE_QUANTITY",
+        min_args: 1,
+        max_args: Some(1),
+        call: parse_quantity,
+    },
+    Function {
+        name: "LENGTH",
+        min_args: 1,
+        max_args: Some(1),
+        call: length,
+    },
+];
+
+/// An aggregate function that is evaluated over the rows of a group
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+/// Finds the aggregate function with the given case-insensitive name
+pub fn lookup_aggregate(name: &str) -> Option<Aggregate> {
+    match name.to_uppercase().as_str() {
+        "COUNT" => Some(Aggregate::Count),
+        "SUM" => Some(Aggregate::Sum),
+        "MIN" => Some(Aggregate::Min),
+        "MAX" => Some(Aggregate::Max),
+        "AVG" => Some(Aggregate::Avg),
+        _ => None,
+    }
+}
+
+impl Aggregate {
+    /// Applies the aggregate function on the values of a group, NULLs are ignored
+    pub fn apply(&self, values: Vec<Value>) -> Value {
+        let values = values
+            .into_iter()
+            .filter(|x| !x.is_null())
+            .collect::<Vec<Value>>();
+
+        match self {
+            Aggregate::Count => Value::Integer(values.len() as i64),
+            Aggregate::Sum | Aggregate::Avg if values.is_empty() => Value::Null,
+            Aggregate::Sum if values.iter().all(|x| matches!(x, Value::Integer(_))) => {
+                Value::Integer(values.iter().filter_map(|x| x.as_i64()).sum())
+            }
+            Aggregate::Sum => Value::Float(values.iter().filter_map(|x| x.as_f64()).sum()),
+            Aggregate::Avg => Value::Float(
+                values.iter().filter_map(|x| x.as_f64()).sum::<f64>() / values.len() as f64,
+            ),
+            Aggregate::Min => values
+                .into_iter()
+                .reduce(|a, b| match b.compare(&a) {
+                    Some(Ordering::Less) => b,
+                    _ => a,
+                })
+                .unwrap_or(Value::Null),
+            Aggregate::Max => values
+                .into_iter()
+                .reduce(|a, b| match b.compare(&a) {
+                    Some(Ordering::Greater) => b,
+                    _ => a,
+                })
+                .unwrap_or(Value::Null),
+        }
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Aggregate::Count => write!(f, "COUNT"),
+            Aggregate::Sum => write!(f, "SUM"),
+            Aggregate::Min => write!(f, "MIN"),
+            Aggregate::Max => write!(f, "MAX"),
+            Aggregate::Avg => write!(f, "AVG"),
+        }
+    }
+}
+
+/// Finds the scalar function with the given case-insensitive name
+pub fn lookup(name: &str) -> Option<&'static Function> {
+    FUNCTIONS.iter().find(|f| f.name.eq_ignore_ascii_case(name))
+}
+
+/// Returns the names of all scalar and aggregate functions
+pub fn names() -> Vec<&'static str> {
+    let mut names = FUNCTIONS.iter().map(|f| f.name).collect::<Vec<&str>>();
+    names.extend(["COUNT", "SUM", "MIN", "MAX", "AVG"]);
+    names
+}
+
+impl Function {
+    /// Returns whether the function accepts the given number of arguments
+    pub fn accepts(&self, n: usize) -> bool {
+        n >= self.min_args && self.max_args.is_none_or(|max| n <= max)
+    }
+
+    pub fn call(&self, args: &[Value]) -> Value {
+        (self.call)(args)
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Returns the string representation of the given value, or `None` for NULL
+fn string_arg(v: &Value) -> Option<String> {
+    match v {
+        Value::Null => None,
+        x => Some(x.to_string()),
+    }
+}
+
+fn lower(args: &[Value]) -> Value {
+    string_arg(&args[0]).map_or(Value::Null, |s| Value::String(s.to_lowercase()))
+}
+
+fn upper(args: &[Value]) -> Value {
+    string_arg(&args[0]).map_or(Value::Null, |s| Value::String(s.to_uppercase()))
+}
+
+/// SPLIT_PART(string, delimiter, n) returns the n-th (1-based) field
+fn split_part(args: &[Value]) -> Value {
+    match (string_arg(&args[0]), string_arg(&args[1]), args[2].as_i64()) {
+        (Some(s), Some(d), Some(n)) if n > 0 => Value::String(
+            s.split(d.as_str())
+                .nth(n as usize - 1)
+                .unwrap_or_default()
+                .to_string(),
+        ),
+        _ => Value::Null,
+    }
+}
+
+/// SUBSTRING(string [FROM start] [FOR count]) with a 1-based start
+fn substring(args: &[Value]) -> Value {
+    let s = match string_arg(&args[0]) {
+        Some(s) => s,
+        None => return Value::Null,
+    };
+    let from = args.get(1).and_then(|x| x.as_i64()).unwrap_or(1).max(1) as usize;
+    let count = args
+        .get(2)
+        .and_then(|x| x.as_i64())
+        .map_or(usize::MAX, |x| x.max(0) as usize);
+
+    Value::String(s.chars().skip(from - 1).take(count).collect())
+}
+
+/// CONCAT(...) joins all arguments, ignoring NULLs
+fn concat(args: &[Value]) -> Value {
+    Value::String(args.iter().filter_map(string_arg).collect())
+}
+
+/// COALESCE(...) returns the first non-NULL argument
+fn coalesce(args: &[Value]) -> Value {
+    args.iter()
+        .find(|x| !x.is_null())
+        .cloned()
+        .unwrap_or(Value::Null)
+}
+
+/// AGE(timestamp) returns the duration elapsed since the given timestamp
+fn age(args: &[Value]) -> Value {
+    args[0]
+        .as_timestamp()
+        .map_or(Value::Null, |t| Value::Duration(Utc::now() - t))
+}
+
+fn now(_: &[Value]) -> Value {
+    Value::Timestamp(Utc::now())
+}
+
+fn parse_quantity(args: &[Value]) -> Value {
+    match &args[0] {
+        Value::Quantity(q) => Value::Quantity(q.clone()),
+        x => string_arg(x)
+            .and_then(|s| Quantity::parse(&s))
+            .map_or(Value::Null, Value::Quantity),
+    }
+}
+
+/// LENGTH(x) returns the number of characters of a string, or elements of an array or object
+fn length(args: &[Value]) -> Value {
+    match &args[0] {
+        Value::Null => Value::Null,
+        Value::List(l) => Value::Integer(l.len() as i64),
+        Value::Object(o) => Value::Integer(o.len() as i64),
+        x => Value::Integer(x.to_string().chars().count() as i64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator;
+    use crate::evaluator::Record;
+    use crate::parser;
+
+    /// Evaluates the given standalone expression
+    fn eval(sql: &str) -> Value {
+        let expr = parser::parse_condition(sql, &Default::default());
+        evaluator::evaluate(
+            &expr,
+            &Record {
+                context: "minikube",
+                namespace: "default",
+                kind: "pod",
+                object: &serde_json::Value::Null,
+            },
+        )
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn lower_converts_the_text_of_any_value() {
+        assert_eq!(eval("LOWER('Kube-System')"), string("kube-system"));
+        assert_eq!(eval("lower(42)"), string("42"));
+        assert_eq!(eval("LOWER(NULL)"), Value::Null);
+    }
+
+    #[test]
+    fn split_part_returns_the_nth_field() {
+        assert_eq!(
+            eval("SPLIT_PART('nginx:1.19:alpine', ':', 2)"),
+            string("1.19")
+        );
+        assert_eq!(eval("SPLIT_PART('nginx', ':', 2)"), string(""));
+        assert_eq!(eval("SPLIT_PART('nginx', ':', 0)"), Value::Null);
+        assert_eq!(eval("SPLIT_PART(NULL, ':', 1)"), Value::Null);
+        assert_eq!(eval("SPLIT_PART('a:b', NULL, 1)"), Value::Null);
+    }
+
+    #[test]
+    fn substring_counts_from_one() {
+        assert_eq!(eval("SUBSTRING('kubernetes' FROM 5 FOR 3)"), string("rne"));
+        assert_eq!(eval("SUBSTRING('kubernetes' FROM 5)"), string("rnetes"));
+        assert_eq!(eval("SUBSTRING('kubernetes' FROM 0 FOR 4)"), string("kube"));
+        assert_eq!(eval("SUBSTRING('kubernetes' FROM 20)"), string(""));
+        assert_eq!(eval("SUBSTRING(NULL FROM 1)"), Value::Null);
+    }
+
+    #[test]
+    fn length_counts_characters_or_elements() {
+        assert_eq!(eval("LENGTH('çay')"), Value::Integer(3));
+        assert_eq!(eval("LENGTH(NULL)"), Value::Null);
+        let length = lookup("LENGTH").unwrap();
+        assert_eq!(
+            length.call(&[Value::List(vec![string("a"), string("b")])]),
+            Value::Integer(2)
+        );
+        assert_eq!(
+            length.call(&[Value::Object(Default::default())]),
+            Value::Integer(0)
+        );
+    }
+
+    #[test]
+    fn parse_quantity_returns_null_for_other_text() {
+        assert_eq!(
+            eval("PARSE_QUANTITY('500m')"),
+            Value::Quantity(Quantity::parse("0.5").unwrap())
+        );
+        assert_eq!(
+            eval("PARSE_QUANTITY('1Gi') > PARSE_QUANTITY('1000Mi')"),
+            Value::Bool(true)
+        );
+        assert_eq!(eval("PARSE_QUANTITY('fast')"), Value::Null);
+        assert_eq!(eval("PARSE_QUANTITY(NULL)"), Value::Null);
+    }
+
+    #[test]
+    fn now_returns_the_current_time() {
+        let before = Utc::now();
+        let now = eval("NOW()").as_timestamp().unwrap();
+        assert!(before <= now && now <= Utc::now());
+    }
+
+    #[test]
+    fn arguments_are_counted() {
+        assert!(lookup("split_part").unwrap().accepts(3));
+        assert!(!lookup("SPLIT_PART").unwrap().accepts(2));
+        assert!(lookup("CONCAT").unwrap().accepts(10));
+        assert!(!lookup("NOW").unwrap().accepts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Function LOWER does not accept 2 argument(s)!")]
+    fn wrong_number_of_arguments_is_rejected() {
+        eval("LOWER('a', 'b')");
+    }
+}
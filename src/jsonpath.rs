@@ -0,0 +1,265 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::evaluator;
+use crate::planner::Segment;
+
+/// A JSONPath template like 'kubectl get -o jsonpath': i.e.
+/// '{.metadata.name}{"\t"}{range .spec.containers[*]}{.image}{" "}{end}'
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    /// The text outside of the braces, or a string literal: i.e. '{"\n"}'
+    Text(String),
+    /// The values at the path: i.e. '{.metadata.name}'
+    Path(Vec<Segment>),
+    /// The items that are rendered for each value at the path, until '{end}'
+    Range(Vec<Segment>, Vec<Item>),
+}
+
+impl Template {
+    /// Parses the given template, returns an error if it is malformed
+    pub fn parse(input: &str) -> Result<Template, String> {
+        let mut stack: Vec<(Vec<Segment>, Vec<Item>)> = vec![];
+        let mut items: Vec<Item> = vec![];
+        let mut text = String::new();
+        let mut chars = input.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '{' {
+                text.push(c);
+                continue;
+            }
+            if !text.is_empty() {
+                items.push(Item::Text(std::mem::take(&mut text)));
+            }
+
+            // the action lasts until the closing brace that is not quoted
+            let mut action = String::new();
+            let mut quote: Option<char> = None;
+            let mut closed = false;
+            while let Some(c) = chars.next() {
+                match (quote, c) {
+                    (None, '}') => {
+                        closed = true;
+                        break;
+                    }
+                    (None, '"') | (None, '\'') => quote = Some(c),
+                    (Some(q), c) if q == c => quote = None,
+                    (Some(_), '\\') => {
+                        action.push(c);
+                        if let Some(x) = chars.next() {
+                            action.push(x);
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+                action.push(c);
+            }
+            if !closed {
+                return Err(format!("Unclosed action in JSONPath template: '{}'", input));
+            }
+
+            let action = action.trim();
+            if action == "end" {
+                let (path, outer) = stack.pop().ok_or_else(|| {
+                    format!("Unexpected {{end}} in JSONPath template: '{}'", input)
+                })?;
+                let inner = std::mem::replace(&mut items, outer);
+                items.push(Item::Range(path, inner));
+            } else if let Some(path) = action.strip_prefix("range ") {
+                stack.push((parse_path(path)?, std::mem::take(&mut items)));
+            } else if action.starts_with('"') {
+                items.push(Item::Text(parse_string(action)?));
+            } else {
+                items.push(Item::Path(parse_path(action)?));
+            }
+        }
+
+        if !stack.is_empty() {
+            return Err(format!("Missing {{end}} in JSONPath template: '{}'", input));
+        }
+        if !text.is_empty() {
+            items.push(Item::Text(text));
+        }
+
+        Ok(Template { items })
+    }
+
+    /// Renders the template for the given object
+    pub fn render(&self, object: &serde_json::Value) -> String {
+        let mut out = String::new();
+        render_items(&self.items, object, &mut out);
+        out
+    }
+}
+
+fn render_items(items: &[Item], object: &serde_json::Value, out: &mut String) {
+    for item in items {
+        match item {
+            Item::Text(t) => out.push_str(t),
+            Item::Path(path) => out.push_str(
+                &evaluator::select(object, path)
+                    .into_iter()
+                    .map(text)
+                    .collect::<Vec<String>>()
+                    .join(" "),
+            ),
+            Item::Range(path, inner) => {
+                let mut values = evaluator::select(object, path);
+                // '{range .spec.containers}' iterates over the array itself
+                if let [serde_json::Value::Array(a)] = values.as_slice() {
+                    values = a.iter().collect();
+                }
+                for v in values {
+                    render_items(inner, v, out);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the given value as text, where strings are not quoted and NULL is empty
+pub fn text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// Parses a string literal of a template: i.e. '"\t"'
+fn parse_string(input: &str) -> Result<String, String> {
+    serde_json::from_str::<String>(input)
+        .map_err(|e| format!("Invalid string in JSONPath template: {}: {}", input, e))
+}
+
+/// Parses a JSONPath expression: i.e. '.spec.containers[0].image', '{.metadata.name}' or
+/// ".metadata.labels['app.kubernetes.io/name']"
+pub fn parse_path(input: &str) -> Result<Vec<Segment>, String> {
+    let error = || format!("Invalid JSONPath: '{}'", input);
+
+    let mut rest = input.trim();
+    if let Some(x) = rest.strip_prefix('{').and_then(|x| x.strip_suffix('}')) {
+        rest = x.trim();
+    }
+    rest = rest
+        .strip_prefix('$')
+        .or_else(|| rest.strip_prefix('@'))
+        .unwrap_or(rest);
+
+    let mut path = vec![];
+    while !rest.is_empty() {
+        if let Some(x) = rest.strip_prefix('[') {
+            let end = x.find(']').ok_or_else(error)?;
+            let accessor = x[..end].trim();
+            rest = &x[end + 1..];
+
+            let quoted = accessor
+                .strip_prefix('\'')
+                .and_then(|x| x.strip_suffix('\''))
+                .or_else(|| accessor.strip_prefix('"').and_then(|x| x.strip_suffix('"')));
+            path.push(match (quoted, accessor) {
+                (Some(f), _) => Segment::Field(f.to_string()),
+                (None, "*") => Segment::Wildcard,
+                (None, i) => Segment::Index(i.parse::<i64>().map_err(|_| error())?),
+            });
+        } else if let Some(x) = rest.strip_prefix('.') {
+            let end = x.find(['.', '[']).unwrap_or(x.len());
+            match &x[..end] {
+                "" if end == x.len() => {}
+                "" => return Err(error()),
+                "*" => path.push(Segment::Wildcard),
+                f => path.push(Segment::Field(f.to_string())),
+            }
+            rest = &x[end..];
+        } else {
+            return Err(error());
+        }
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, object: serde_json::Value) -> String {
+        Template::parse(template).unwrap().render(&object)
+    }
+
+    #[test]
+    fn paths_and_literals_are_rendered() {
+        let pod = json!({"metadata": {"name": "a", "labels": {"app": "web"}}});
+        assert_eq!(
+            render(
+                r#"name={.metadata.name}{"\t"}{.metadata.labels.app}"#,
+                pod.clone()
+            ),
+            "name=a\tweb"
+        );
+        assert_eq!(render("{.metadata.missing}", pod), "");
+    }
+
+    #[test]
+    fn ranges_iterate_until_end() {
+        let pod = json!({"spec": {"containers": [{"image": "nginx"}, {"image": "redis"}]}});
+        assert_eq!(
+            render(
+                r#"{range .spec.containers[*]}{.image}{" "}{end}"#,
+                pod.clone()
+            ),
+            "nginx redis "
+        );
+        assert_eq!(
+            render(r#"{range .spec.containers}[{.image}]{end}"#, pod),
+            "[nginx][redis]"
+        );
+    }
+
+    #[test]
+    fn braces_in_literals_do_not_close_the_action() {
+        assert_eq!(
+            Template::parse(r#"{"}"}"#),
+            Ok(Template {
+                items: vec![Item::Text("}".to_string())]
+            })
+        );
+    }
+
+    #[test]
+    fn malformed_templates_are_rejected() {
+        assert!(Template::parse("{.metadata.name")
+            .unwrap_err()
+            .contains("Unclosed action"));
+        assert!(Template::parse("{end}")
+            .unwrap_err()
+            .contains("Unexpected {end}"));
+        assert!(Template::parse("{range .items}{.name}")
+            .unwrap_err()
+            .contains("Missing {end}"));
+    }
+}
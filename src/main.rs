@@ -18,127 +18,324 @@
 // CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
 mod api_builder;
+mod command;
+mod config;
+mod evaluator;
+mod executor;
+mod function;
+mod jsonpath;
 mod parser;
 mod planner;
 mod printer;
+mod result;
+mod schema;
+mod shell;
+mod summary;
 mod validator;
+mod value;
+mod watcher;
 
-use crate::api_builder::ApiBuilder;
-use crate::parser::ResourceType;
-use crate::printer::Printer;
+use crate::config::Config;
+use crate::executor::{Executor, Options};
+use crate::printer::Format;
 use anyhow::Result;
-use clap::{App, Arg};
-use kube::api::ListParams;
-use sqlparser::ast::BinaryOperator;
+use clap::{App, Arg, ArgMatches, SubCommand};
 use std::fs::File;
 use std::io::prelude::*;
 use std::str::FromStr;
+use std::time::Duration;
+
+/// The seconds between the runs of the query, if only '--timeout' is given
+const DEFAULT_INTERVAL: u64 = 5;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let output_help = format!(
+        "One of: {}, jsonpath=TEMPLATE, custom-columns=SPEC [default: nested]",
+        Format::names().join(", ")
+    );
     let matches = App::new("kubesql")
         .version("0.1.0")
         .author("Dentrax <furkan.turkal@hotmail.com>")
         .about("kubesql is an experimental tool for querying your Kubernetes API Server using SQL")
+        .after_help("Defaults of the global options are read from ~/.config/kubesql/config.toml")
         .arg(
-            Arg::with_name("query")
-                .short("q")
-                .long("query")
-                .multiple(false)
-                .overrides_with("file")
+            Arg::with_name("kubeconfig")
+                .long("kubeconfig")
+                .value_name("FILE[:FILE...]")
+                .help("Paths of the kubeconfig files to merge like $KUBECONFIG, instead of the default ones")
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("context")
+                .long("context")
+                .value_name("CONTEXT")
+                .help("The context to use instead of the current one, i.e. for the queries without FROM")
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("namespace")
+                .short("n")
+                .long("namespace")
+                .value_name("NAMESPACE")
+                .help("The namespace to query if there is no namespace given in SELECT [default: default]")
+                .global(true)
                 .takes_value(true),
         )
         .arg(
-            Arg::with_name("file")
-                .short("f")
-                .long("file")
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
                 .multiple(false)
-                .overrides_with("query")
-                .value_name("FILE")
+                .value_name("FORMAT")
+                .help(&output_help)
+                .validator(|x| Format::from_str(&x).map(|_| ()))
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("concurrency")
+                .long("concurrency")
+                .value_name("N")
+                .help("How many API calls are made at once [default: 4]")
+                .validator(|x| match x.parse::<usize>() {
+                    Ok(n) if n > 0 => Ok(()),
+                    _ => Err(format!("Invalid concurrency: '{}', i.e. '8'", x)),
+                })
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .value_name("DURATION")
+                .help("Fails if the expectations are not met in time, i.e. '5m'")
+                .validator(|x| config::parse_duration(&x).map(|_| ()))
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("request-timeout")
+                .long("request-timeout")
+                .value_name("DURATION")
+                .help("Fails if the API server does not respond to a call in time, i.e. '30s'")
+                .validator(|x| config::parse_duration(&x).map(|_| ()))
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("param")
+                .long("param")
+                .value_name("NAME=VALUE")
+                .help("Binds the placeholder $NAME or :NAME of the query to the value, i.e. 'ns=default'")
+                .validator(|x| parser::parse_parameter(&x).map(|_| ()))
+                .multiple(true)
+                .number_of_values(1)
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no-headers")
+                .long("no-headers")
+                .help("Omits the header row of the csv, tsv and custom-columns outputs")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("no-managed-fields")
+                .long("no-managed-fields")
+                .help("Omits the managedFields of the objects in the yaml output")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("no-field-check")
+                .long("no-field-check")
+                .help("Does not validate the field paths against the schemas of the API server")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("namespace-check")
+                .long("namespace-check")
+                .value_name("MODE")
+                .help("Whether the namespaces that are not found fail the query, are warned about, or are not checked [default: error]")
+                .possible_values(&["error", "warn", "skip"])
+                .global(true)
                 .takes_value(true),
         )
+        .args(&query_args())
+        .subcommand(
+            SubCommand::with_name("query")
+                .about("Runs the given query, which is the default when no subcommand is given")
+                .arg(
+                    Arg::with_name("sql")
+                        .value_name("SQL")
+                        .conflicts_with_all(&["query", "file"])
+                        .index(1),
+                )
+                .args(&query_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("shell")
+                .about("Starts an interactive shell, where queries are terminated by ';'"),
+        )
+        .subcommand(
+            SubCommand::with_name("explain")
+                .about("Prints how the given query is run, without calling the API server")
+                .arg(
+                    Arg::with_name("sql")
+                        .value_name("SQL")
+                        .conflicts_with_all(&["query", "file"])
+                        .index(1),
+                )
+                .args(&sql_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("describe")
+                .about("Prints the fields of the given kind or field path, i.e. 'pod.spec.containers'")
+                .arg(
+                    Arg::with_name("target")
+                        .value_name("KIND[.PATH]")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("contexts")
+                .about("Lists the contexts of the kubeconfig, marking the one that is used by default"),
+        )
         .get_matches();
 
-    let sql = if matches.is_present("query") {
-        matches.value_of("query").unwrap().to_string()
-    } else if matches.is_present("file") {
-        let v = matches.value_of("file").unwrap();
-        let mut file = File::open(v).expect("Unable to open the query file");
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .expect("Unable to read the query file");
-        contents
-    } else {
-        panic!("Either --query or --file required")
+    // the global options are propagated to the subcommand
+    let (subcommand, args) = match matches.subcommand() {
+        (name, Some(sub)) => (name, sub),
+        _ => ("query", &matches),
     };
 
-    let api_queries = parser::parse_sql(&sql);
+    let config = Config::load(args);
 
-    let mut apis: Vec<ApiBuilder> = Vec::new();
+    let parameters = args
+        .values_of("param")
+        .map(|x| x.map(|p| parser::parse_parameter(p).unwrap()).collect())
+        .unwrap_or_default();
 
-    let kubeconfig = parser::parse_kubeconfig();
+    let until = args
+        .value_of("until")
+        .map(|x| parser::parse_condition(x, &parameters));
+    let fail_if_rows = args.is_present("fail-if-rows");
 
-    validator::validate_contexts(kubeconfig, &api_queries.contexts);
+    // the query is polled with a default interval if only the timeout is given
+    let interval = match args.value_of("interval") {
+        Some(i) => Some(config::parse_duration(i).unwrap()),
+        None if until.is_some() || fail_if_rows => config
+            .timeout
+            .map(|_| Duration::from_secs(DEFAULT_INTERVAL)),
+        None => None,
+    };
 
-    for ctx in &api_queries.contexts {
-        for ns in &api_queries.namespaces {
-            apis.push(
-                ApiBuilder::builder()
-                    .context(ctx.clone())
-                    .namespace(ns.clone())
-                    .queries(api_queries.queries.as_slice())
-                    .build()
-                    .await?,
-            )
+    let options = Options {
+        summary: args.is_present("summary"),
+        until,
+        fail_if_rows,
+        interval,
+        parameters,
+    };
+    let mut executor = Executor::new(config, options);
+
+    match subcommand {
+        "shell" => shell::run(executor).await,
+        "explain" => {
+            command::explain(&executor, &read_sql(args));
+            Ok(())
+        }
+        "describe" => command::describe(&mut executor, args.value_of("target").unwrap()).await,
+        "contexts" => {
+            command::contexts(executor.config());
+            Ok(())
+        }
+        _ => {
+            let sql = read_sql(args);
+            match args.is_present("watch") {
+                true => executor.watch(&sql).await,
+                false => executor.run(&sql).await,
+            }
         }
     }
+}
 
-    let mut printer = Printer::builder()
-        .contexts(&api_queries.contexts)
-        .namespaces(&api_queries.namespaces)
-        .queries(api_queries.queries.as_slice());
-
-    //1. Query { key: None, kind: "pod", field1: "status", field2: "phase", eq: "Running", op: Eq }
-    //2. Query { key: Some(And), kind: "deployment", field1: "metadata", field2: "name", eq: "my-awesome-deployment", op: Eq }
-    for q in api_queries.queries.clone() {
-        // a.k.a '--field-selector': https://v1-18.docs.kubernetes.io/docs/concepts/overview/working-with-objects/field-selectors/
-        let list_params =
-            ListParams::default().fields(&format!("{}.{}={}", q.field1, q.field2, q.eq));
+/// Returns the arguments that give the SQL
+fn sql_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("query")
+            .short("q")
+            .long("query")
+            .multiple(false)
+            .overrides_with("file")
+            .takes_value(true),
+        Arg::with_name("file")
+            .short("f")
+            .long("file")
+            .multiple(false)
+            .overrides_with("query")
+            .value_name("FILE")
+            .help("Reads the query from the file, or from the standard input if it is '-'")
+            .takes_value(true),
+    ]
+}
 
-        for api in &apis {
-            let mut found: bool = false;
-            match parser::ResourceType::from_str(&q.kind.to_lowercase()).unwrap() {
-                ResourceType::Deployment => {
-                    let o = api.get_deployment().list(&list_params).await?;
-                    if !o.items.is_empty() {
-                        printer.insert_deployments(api.get_context(), api.get_namespace(), o);
-                        found = true;
-                    }
-                }
-                ResourceType::Pod => {
-                    let o = api.get_pod().list(&list_params).await?;
-                    if !o.items.is_empty() {
-                        printer.insert_pods(api.get_context(), api.get_namespace(), o);
-                        found = true;
-                    }
-                }
-            }
+/// Returns the arguments of running a query
+fn query_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let mut args = sql_args();
+    args.extend(vec![
+        Arg::with_name("summary")
+            .long("summary")
+            .help("Prints the object counts, API calls and timings after the results")
+            .conflicts_with("watch"),
+        Arg::with_name("watch")
+            .short("w")
+            .long("watch")
+            .help("Keeps running the query, printing the results again as the objects change"),
+        Arg::with_name("until")
+            .long("until")
+            .value_name("CONDITION")
+            .help("Fails unless the objects satisfying the query meet the condition, i.e. 'COUNT(*) = 0'")
+            .conflicts_with("watch")
+            .takes_value(true),
+        Arg::with_name("fail-if-rows")
+            .long("fail-if-rows")
+            .help("Fails if the query returns any row")
+            .conflicts_with("watch"),
+        Arg::with_name("interval")
+            .long("interval")
+            .value_name("DURATION")
+            .help("Runs the query again every interval until the expectations are met, i.e. '10s'")
+            .validator(|x| config::parse_duration(&x).map(|_| ()))
+            .conflicts_with("watch")
+            .takes_value(true),
+    ]);
+    args
+}
 
-            // we will decide according to given operator, in case if resource not found or empty
-            if !found {
-                if let Some(k) = &q.key {
-                    if *k == BinaryOperator::And {
-                        panic!(
-                            "No resource found: 'kubectl get {} --field-selector={}.{}={}'",
-                            q.kind, q.field1, q.field2, q.eq
-                        );
-                    }
-                }
-            }
+/// Returns the SQL that is given as the argument, with '--query' or in '--file'
+fn read_sql(args: &ArgMatches) -> String {
+    if let Some(sql) = args.value_of("sql") {
+        sql.to_string()
+    } else if args.is_present("query") {
+        args.value_of("query").unwrap().to_string()
+    } else if args.is_present("file") {
+        let v = args.value_of("file").unwrap();
+        let mut contents = String::new();
+        if v == "-" {
+            std::io::stdin()
+                .read_to_string(&mut contents)
+                .expect("Unable to read the query from the standard input");
+        } else {
+            let mut file = File::open(v).expect("Unable to open the query file");
+            file.read_to_string(&mut contents)
+                .expect("Unable to read the query file");
         }
+        contents
+    } else {
+        panic!("Either --query or --file required")
     }
-
-    printer.print();
-
-    Ok(())
 }
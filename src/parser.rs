@@ -18,20 +18,31 @@
 // CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
 use crate::planner;
-use crate::planner::{Object, Query};
-use kube::config::Kubeconfig;
-use sqlparser::ast::{SelectItem, SetExpr, Statement, TableFactor};
+use crate::planner::{Expression, Object, OrderBy, Parameters, Projection, Query, VirtualColumn};
+use crate::value;
+use kube::config::{Context, Kubeconfig, NamedContext};
+use sqlparser::ast::{Expr, SelectItem, SetExpr, Statement, TableFactor};
 use sqlparser::dialect::GenericDialect;
 use sqlparser::parser::Parser;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::env;
 use std::fmt;
 use std::fmt::Formatter;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 #[derive(Debug)]
 pub struct ApiQueries {
+    /// The namespaces given in SELECT, which are left to the caller to default if there is none
     pub namespaces: Vec<String>,
+    /// The contexts given in FROM, which is empty if the current context is queried
     pub contexts: Vec<String>,
     pub queries: Vec<Query>,
+    pub projections: Vec<Projection>,
+    pub group_by: Vec<Expression>,
+    pub order_by: Vec<OrderBy>,
+    /// Whether the duplicate rows are removed: i.e. 'SELECT DISTINCT ...'
+    pub distinct: bool,
 }
 
 #[derive(Debug, Hash, Eq, PartialEq)]
@@ -56,17 +67,126 @@ impl FromStr for ResourceType {
         match input {
             "deployment" => Ok(ResourceType::Deployment),
             "pod" => Ok(ResourceType::Pod),
-            _ => panic!("Unexpected ResourceType for {}", input),
+            _ => Err(()),
         }
     }
 }
 
-pub(crate) fn parse_sql(sql: &str) -> ApiQueries {
+impl ResourceType {
+    /// Returns all the supported kinds
+    pub fn all() -> &'static [ResourceType] {
+        &[ResourceType::Deployment, ResourceType::Pod]
+    }
+
+    /// Returns the name of the kind in the OpenAPI definitions of the API server
+    pub fn definition(&self) -> &'static str {
+        match self {
+            ResourceType::Deployment => "io.k8s.api.apps.v1.Deployment",
+            ResourceType::Pod => "io.k8s.api.core.v1.Pod",
+        }
+    }
+
+    /// Returns the fields that the API server supports as '--field-selector'
+    pub fn field_selectors(&self) -> &'static [&'static str] {
+        match self {
+            ResourceType::Deployment => &["metadata.name", "metadata.namespace"],
+            ResourceType::Pod => &[
+                "metadata.name",
+                "metadata.namespace",
+                "spec.nodeName",
+                "spec.restartPolicy",
+                "spec.schedulerName",
+                "spec.serviceAccountName",
+                "status.phase",
+                "status.podIP",
+                "status.nominatedNodeName",
+            ],
+        }
+    }
+}
+
+/// `-` is an incorrect char for SQL identifiers, so the identifiers that contain it are quoted:
+/// i.e. 'labels.app-name' becomes 'labels."app-name"', and 'kube-system' becomes '"kube-system"'.
+/// Array accessors are not supported by SQL identifiers either, so we quote them: i.e.
+/// 'spec.containers[0].image' becomes 'spec.containers."[0]".image'. Placeholders are quoted
+/// as well, in order to be planned as parameters: i.e. '$1' becomes '"$1"'
+fn escape_identifiers(sql: &str) -> String {
+    let mut quote: Option<char> = None;
+    let mut prev = ' ';
+    let mut escaped = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let placeholder = match (quote, c, chars.peek()) {
+            (None, '$', Some(n)) => n.is_ascii_digit(),
+            // '::' is a cast
+            (None, ':', Some(n)) => prev != ':' && (n.is_ascii_alphabetic() || *n == '_'),
+            _ => false,
+        };
+        if placeholder && !(prev.is_ascii_alphanumeric() || prev == '_') {
+            escaped.push('"');
+            escaped.push(c);
+            while let Some(n) =
+                chars.next_if(|x| x.is_ascii_alphanumeric() || ['_', '-'].contains(x))
+            {
+                escaped.push(n);
+            }
+            escaped.push('"');
+            prev = '"';
+            continue;
+        }
+
+        if quote.is_none()
+            && (c.is_ascii_alphabetic() || c == '_')
+            && !(prev.is_ascii_alphanumeric() || prev == '_')
+        {
+            let mut word = c.to_string();
+            while let Some(n) =
+                chars.next_if(|x| x.is_ascii_alphanumeric() || ['_', '-'].contains(x))
+            {
+                word.push(n);
+            }
+            // the trailing dashes are operators: i.e. 'a--b'
+            let identifier = word.trim_end_matches('-');
+            match identifier.contains('-') {
+                true => escaped.push_str(&format!("\"{}\"", identifier)),
+                false => escaped.push_str(identifier),
+            }
+            escaped.push_str(&word[identifier.len()..]);
+            prev = word.chars().last().unwrap();
+            continue;
+        }
+
+        match (quote, c) {
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                escaped.push(c);
+            }
+            (Some(']'), ']') => {
+                quote = None;
+                escaped.push_str("]\"");
+            }
+            (Some(q), _) if q == c => {
+                quote = None;
+                escaped.push(c);
+            }
+            (None, '[') => {
+                quote = Some(']');
+                escaped.push_str(".\"[");
+            }
+            _ => escaped.push(c),
+        }
+        prev = c;
+    }
+
+    escaped
+}
+
+/// Parses the given query, binding its placeholders to the given values
+pub(crate) fn parse_sql(sql: &str, parameters: &Parameters) -> ApiQueries {
     let dialect = GenericDialect {};
 
-    // `-` is an incorrect char for SQL Queries, so we need to replace with another char
-    // We will undo this replace during parsing stage
-    let sql_replace = sql.replace("-", "_");
+    let sql_replace = escape_identifiers(sql);
 
     // Parse the given SQL to AST
     let mut ast = Parser::parse_sql(&dialect, &sql_replace).unwrap();
@@ -82,23 +202,57 @@ pub(crate) fn parse_sql(sql: &str) -> ApiQueries {
         namespaces: vec![],
         contexts: vec![],
         queries: vec![],
+        projections: vec![],
+        group_by: vec![],
+        order_by: vec![],
+        distinct: false,
     };
 
+    if query.with.is_some() {
+        panic!("WITH statement is not supported!")
+    }
+    if query.limit.is_some() || query.offset.is_some() || query.fetch.is_some() {
+        panic!("LIMIT, OFFSET and FETCH statements are not supported!")
+    }
+
+    // ORDER BY ...
+    for o in query.order_by {
+        queries.order_by.push(OrderBy {
+            expr: planner::plan_scalar(o.expr),
+            asc: o.asc.unwrap_or(true),
+        });
+    }
+
     match query.body {
         SetExpr::Select(s) => {
             if s.projection.is_empty() {
                 panic!("SELECT statement is required to call the given namespace(s)!")
             }
 
+            // SELECT DISTINCT ...
+            queries.distinct = s.distinct;
+
             // SELECT ...
             for p in s.projection {
                 match p {
-                    SelectItem::UnnamedExpr(o) => {
-                        queries.namespaces.push(o.to_string().replace("_", "-"));
+                    SelectItem::UnnamedExpr(Expr::Identifier(o))
+                        if planner::parameter(&o).is_some() =>
+                    {
+                        queries
+                            .namespaces
+                            .push(parameter(&planner::parameter(&o).unwrap(), parameters));
+                    }
+                    SelectItem::UnnamedExpr(Expr::Identifier(o))
+                        if VirtualColumn::from_str(&o.value).is_err() =>
+                    {
+                        queries.namespaces.push(o.value);
                     }
-                    SelectItem::ExprWithAlias { .. } => {
-                        panic!("SELECT statement does not support ExprWithAlias selector!")
+                    SelectItem::UnnamedExpr(o) => {
+                        queries.projections.push(planner::plan_projection(o, None))
                     }
+                    SelectItem::ExprWithAlias { expr, alias } => queries
+                        .projections
+                        .push(planner::plan_projection(expr, Some(alias.value))),
                     SelectItem::QualifiedWildcard(_) => {
                         panic!("SELECT statement does not support QualifiedWildcard selector!")
                     }
@@ -108,10 +262,6 @@ pub(crate) fn parse_sql(sql: &str) -> ApiQueries {
                 }
             }
 
-            if s.from.is_empty() {
-                panic!("FROM statement is required to call the given context(s)!")
-            }
-
             // FROM ...
             for f in s.from {
                 if !f.joins.is_empty() {
@@ -134,7 +284,18 @@ pub(crate) fn parse_sql(sql: &str) -> ApiQueries {
                         if !with_hints.is_empty() {
                             panic!("FROM statement does not support Table HINT!")
                         }
-                        queries.contexts.push(name.to_string().replace("_", "-"));
+                        match name.0.as_slice() {
+                            [i] if planner::parameter(i).is_some() => queries
+                                .contexts
+                                .push(parameter(&planner::parameter(i).unwrap(), parameters)),
+                            _ => queries.contexts.push(
+                                name.0
+                                    .into_iter()
+                                    .map(|x| x.value)
+                                    .collect::<Vec<String>>()
+                                    .join("."),
+                            ),
+                        }
                     }
                     TableFactor::Derived { .. } => {
                         panic!("FROM statement does not support Derived!")
@@ -148,15 +309,30 @@ pub(crate) fn parse_sql(sql: &str) -> ApiQueries {
                 }
             }
 
+            if let Some(h) = s.having {
+                panic!("HAVING statement is not supported, got: {}", h)
+            }
+            if s.top.is_some() || !s.lateral_views.is_empty() {
+                panic!("SELECT statement does not support TOP and LATERAL VIEW!")
+            }
+            if !s.cluster_by.is_empty() || !s.distribute_by.is_empty() || !s.sort_by.is_empty() {
+                panic!("CLUSTER BY, DISTRIBUTE BY and SORT BY statements are not supported!")
+            }
+
+            // GROUP BY ...
+            for g in s.group_by {
+                queries.group_by.push(planner::plan_scalar(g));
+            }
+
             // WHERE
             if let Some(w) = s.selection {
-                let plan = planner::plan_expr(w);
-                match plan {
+                match planner::plan_expr(w) {
                     Object::Queries(q) => queries.queries = q,
                     Object::Query(q) => queries.queries.push(q),
-                    _ => {
-                        panic!("Unable to handle unsupported query plan: {:?}", plan)
-                    }
+                    Object::Expression(e) => panic!(
+                        "WHERE statement requires a kind to query: i.e. 'pod.status.phase', got: {}",
+                        e
+                    ),
                 }
             } else {
                 panic!("WHERE statement is required in order to set --field-selector!")
@@ -167,10 +343,356 @@ pub(crate) fn parse_sql(sql: &str) -> ApiQueries {
         }
     }
 
+    // the placeholders are bound once everything is planned
+    for q in queries.queries.iter_mut() {
+        q.predicate = planner::bind(q.predicate.clone(), parameters);
+    }
+    for p in queries.projections.iter_mut() {
+        p.expr = planner::bind(p.expr.clone(), parameters);
+    }
+    for g in queries.group_by.iter_mut() {
+        *g = planner::bind(g.clone(), parameters);
+    }
+    for o in queries.order_by.iter_mut() {
+        o.expr = planner::bind(o.expr.clone(), parameters);
+    }
+
     queries
 }
 
-pub(crate) fn parse_kubeconfig() -> Kubeconfig {
-    kube::config::Kubeconfig::read()
-        .unwrap_or_else(|err| panic!("Could not read KUBECONFIG: {:?}", err))
+/// Returns the value of the given placeholder as a name: i.e. the namespace of 'SELECT :ns'
+fn parameter(name: &str, parameters: &Parameters) -> String {
+    match planner::bind(Expression::Parameter(name.to_string()), parameters) {
+        Expression::Literal(v) => v.to_string(),
+        e => unreachable!("Unexpected bound parameter: {}", e),
+    }
+}
+
+/// Parses the given `name=value` of '--param', where the value is typed as a number, boolean or
+/// NULL if it looks like one, and as a string otherwise or if it is quoted: i.e. `name='1'`
+pub fn parse_parameter(input: &str) -> Result<(String, value::Value), String> {
+    let (name, v) = input
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid parameter: '{}', i.e. 'name=value'", input))?;
+    let name = name.trim_start_matches(['$', ':']).to_string();
+
+    let value = if v.len() >= 2 && v.starts_with('\'') && v.ends_with('\'') {
+        value::Value::String(v[1..v.len() - 1].to_string())
+    } else if v.eq_ignore_ascii_case("null") {
+        value::Value::Null
+    } else if let Ok(b) = v.parse::<bool>() {
+        value::Value::Bool(b)
+    } else if let Ok(i) = v.parse::<i64>() {
+        value::Value::Integer(i)
+    } else if let Ok(x) = v.parse::<f64>() {
+        value::Value::Float(x)
+    } else {
+        value::Value::String(v.to_string())
+    };
+
+    Ok((name, value))
+}
+
+/// Parses the given standalone expression: i.e. `COUNT(*) = 0`
+pub(crate) fn parse_condition(sql: &str, parameters: &Parameters) -> Expression {
+    let dialect = GenericDialect {};
+
+    let sql_replace = escape_identifiers(sql);
+
+    let tokens = Tokenizer::new(&dialect, &sql_replace)
+        .tokenize()
+        .unwrap_or_else(|err| panic!("Unable to parse the condition '{}': {:?}", sql, err));
+
+    let mut parser = Parser::new(tokens, &dialect);
+    let expr = parser
+        .parse_expr()
+        .unwrap_or_else(|err| panic!("Unable to parse the condition '{}': {}", sql, err));
+    if parser.peek_token() != Token::EOF {
+        panic!(
+            "Unexpected '{}' after the condition '{}'",
+            parser.peek_token(),
+            expr
+        );
+    }
+
+    planner::bind(planner::plan_scalar(expr), parameters)
+}
+
+/// The name of the context of the cluster that kubesql runs in, i.e. as a CronJob
+pub(crate) const IN_CLUSTER: &str = "in-cluster";
+
+/// Reads and merges the given kubeconfig files, or the default ones: i.e. '$KUBECONFIG' or
+/// '~/.kube/config'. Inside a pod, the `in-cluster` context is added, which is the current one
+/// if there is no kubeconfig.
+pub(crate) fn parse_kubeconfig(paths: &[PathBuf]) -> Kubeconfig {
+    let in_cluster = env::var("KUBERNETES_SERVICE_HOST").is_ok();
+    let mut kubeconfig = match paths.is_empty() {
+        true => match kube::config::Kubeconfig::read() {
+            Ok(k) => k,
+            Err(_) if in_cluster => Kubeconfig {
+                current_context: Option::from(IN_CLUSTER.to_string()),
+                ..Default::default()
+            },
+            Err(err) => panic!("Could not read KUBECONFIG: {:?}", err),
+        },
+        false => paths
+            .iter()
+            .map(|p| {
+                kube::config::Kubeconfig::read_from(p)
+                    .unwrap_or_else(|err| panic!("Could not read {}: {:?}", p.display(), err))
+            })
+            .reduce(merge_kubeconfig)
+            .unwrap(),
+    };
+
+    if in_cluster && kubeconfig.contexts.iter().all(|x| x.name != IN_CLUSTER) {
+        kubeconfig.contexts.push(NamedContext {
+            name: IN_CLUSTER.to_string(),
+            context: Context {
+                cluster: IN_CLUSTER.to_string(),
+                user: IN_CLUSTER.to_string(),
+                namespace: std::fs::read_to_string(
+                    "/var/run/secrets/kubernetes.io/serviceaccount/namespace",
+                )
+                .ok(),
+                extensions: None,
+            },
+        });
+    }
+    kubeconfig
+}
+
+/// Whether the given context is the `in-cluster` one, which is not from a kubeconfig file
+pub(crate) fn is_in_cluster(kubeconfig: &Kubeconfig, context: &str) -> bool {
+    context == IN_CLUSTER
+        && kubeconfig.clusters.iter().all(|x| x.name != IN_CLUSTER)
+        && kubeconfig
+            .contexts
+            .iter()
+            .any(|x| x.name == IN_CLUSTER && x.context.cluster == IN_CLUSTER)
+}
+
+/// Merges the given kubeconfigs like `KUBECONFIG` does: the first one that sets a value wins,
+/// i.e. the 'current-context', and so does the first entry of the same name.
+/// https://kubernetes.io/docs/concepts/configuration/organize-cluster-access-kubeconfig/#merging-kubeconfig-files
+fn merge_kubeconfig(mut first: Kubeconfig, next: Kubeconfig) -> Kubeconfig {
+    fn append<T>(entries: &mut Vec<T>, next: Vec<T>, name: fn(&T) -> &String) {
+        for entry in next {
+            if entries.iter().all(|x| name(x) != name(&entry)) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    append(&mut first.clusters, next.clusters, |x| &x.name);
+    append(&mut first.auth_infos, next.auth_infos, |x| &x.name);
+    append(&mut first.contexts, next.contexts, |x| &x.name);
+    first.current_context = first.current_context.or(next.current_context);
+    first.preferences = first.preferences.or(next.preferences);
+    first.extensions = first.extensions.or(next.extensions);
+    first.kind = first.kind.or(next.kind);
+    first.api_version = first.api_version.or(next.api_version);
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::Record;
+    use crate::value::Value;
+    use serde_json::json;
+    use sqlparser::ast::BinaryOperator;
+
+    fn matches(sql: &str, object: serde_json::Value) -> bool {
+        let q = parse_sql(sql, &Default::default());
+        assert_eq!(q.queries.len(), 1, "{:?}", q.queries);
+        q.queries[0].matches(&Record {
+            context: "minikube",
+            namespace: "default",
+            kind: "pod",
+            object: &object,
+        })
+    }
+
+    #[test]
+    fn conditions_on_the_same_kind_are_a_single_query() {
+        let pod =
+            json!({"status": {"phase": "Running", "containerStatuses": [{"restartCount": 1}]}});
+        let and = "SELECT default FROM minikube WHERE pod.status.phase = 'Running' AND pod.status.containerStatuses.restartCount > 3";
+        assert!(!matches(and, pod.clone()));
+        let or = "SELECT default FROM minikube WHERE pod.status.phase = 'Running' OR pod.status.containerStatuses.restartCount > 3";
+        assert!(matches(or, pod));
+    }
+
+    #[test]
+    fn identifiers_with_dashes_are_quoted() {
+        assert_eq!(
+            escape_identifiers("SELECT kube-system FROM kind-kind WHERE pod.metadata.labels.app-name = 'my-app'"),
+            "SELECT \"kube-system\" FROM \"kind-kind\" WHERE pod.metadata.labels.\"app-name\" = 'my-app'"
+        );
+        assert_eq!(
+            escape_identifiers("pod.spec.containers[0].image = $1 AND x::text = :name"),
+            "pod.spec.containers.\"[0]\".image = \"$1\" AND x::text = \":name\""
+        );
+        assert_eq!(escape_identifiers("a - b - 1"), "a - b - 1");
+    }
+
+    #[test]
+    fn namespaces_and_contexts_keep_their_names() {
+        let q = parse_sql(
+            "SELECT kube-system, my_ns FROM kind-kind, gke_project_zone WHERE pod.status.phase = 'Running'",
+            &Default::default(),
+        );
+        assert_eq!(q.namespaces, vec!["kube-system", "my_ns"]);
+        assert_eq!(q.contexts, vec!["kind-kind", "gke_project_zone"]);
+    }
+
+    #[test]
+    fn label_keys_with_dashes_are_matched() {
+        let pod = json!({"metadata": {"labels": {"app-name": "x", "app_name": "y"}}});
+        let sql = "SELECT default FROM minikube WHERE pod.metadata.labels.app-name = 'x'";
+        assert!(matches(sql, pod));
+    }
+
+    #[test]
+    #[should_panic(expected = "HAVING statement is not supported")]
+    fn having_is_rejected() {
+        parse_sql(
+            "SELECT default, pod.spec.nodeName, COUNT(*) FROM minikube WHERE pod.status.phase = 'Running' GROUP BY pod.spec.nodeName HAVING COUNT(*) > 1",
+            &Default::default(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "LIMIT, OFFSET and FETCH statements are not supported")]
+    fn limit_is_rejected() {
+        parse_sql(
+            "SELECT default FROM minikube WHERE pod.status.phase = 'Running' LIMIT 1",
+            &Default::default(),
+        );
+    }
+
+    #[test]
+    fn conditions_on_other_kinds_are_separate_queries() {
+        let q = parse_sql(
+            "SELECT default FROM minikube WHERE pod.status.phase = 'Running' AND deployment.spec.replicas = 0 AND pod.metadata.name = 'a'",
+            &Default::default(),
+        );
+        let kinds = q
+            .queries
+            .iter()
+            .map(|x| x.kind.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(kinds, vec!["pod", "deployment"]);
+        assert_eq!(q.queries[1].key, Some(BinaryOperator::And));
+    }
+
+    #[test]
+    fn kubeconfigs_are_merged_with_the_first_entry_winning() {
+        let kubeconfig = |yaml: &str| serde_yaml::from_str::<Kubeconfig>(yaml).unwrap();
+        let first = kubeconfig(
+            "
+clusters:
+  - name: minikube
+    cluster:
+      server: https://first
+contexts:
+  - name: minikube
+    context:
+      cluster: minikube
+      user: admin
+users:
+  - name: admin
+    user: {}
+",
+        );
+        let next = kubeconfig(
+            "
+current-context: kind
+clusters:
+  - name: minikube
+    cluster:
+      server: https://next
+  - name: kind
+    cluster:
+      server: https://kind
+contexts:
+  - name: kind
+    context:
+      cluster: kind
+      user: admin
+users:
+  - name: admin
+    user: {}
+",
+        );
+
+        let merged = merge_kubeconfig(first, next);
+        let servers = merged
+            .clusters
+            .iter()
+            .map(|x| (x.name.as_str(), x.cluster.server.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+        assert_eq!(
+            servers,
+            vec![("minikube", "https://first"), ("kind", "https://kind")]
+        );
+        let contexts = merged
+            .contexts
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(contexts, vec!["minikube", "kind"]);
+        assert_eq!(merged.auth_infos.len(), 1);
+        assert_eq!(merged.current_context, Some("kind".to_string()));
+    }
+
+    #[test]
+    fn parameters_are_typed_unless_quoted() {
+        let parse = |s| parse_parameter(s).unwrap();
+        assert_eq!(
+            parse("ns=default"),
+            ("ns".to_string(), Value::String("default".to_string()))
+        );
+        assert_eq!(parse("$1=3"), ("1".to_string(), Value::Integer(3)));
+        assert_eq!(
+            parse(":ratio=0.5"),
+            ("ratio".to_string(), Value::Float(0.5))
+        );
+        assert_eq!(
+            parse("ready=true"),
+            ("ready".to_string(), Value::Bool(true))
+        );
+        assert_eq!(parse("node=NULL"), ("node".to_string(), Value::Null));
+        assert_eq!(
+            parse("id='1'"),
+            ("id".to_string(), Value::String("1".to_string()))
+        );
+        assert_eq!(
+            parse("sel=a=b"),
+            ("sel".to_string(), Value::String("a=b".to_string()))
+        );
+        assert!(parse_parameter("default")
+            .unwrap_err()
+            .contains("Invalid parameter"));
+    }
+
+    #[test]
+    fn placeholders_are_bound_to_the_parameters() {
+        let parameters = ["ns=kube-system", "1=a"]
+            .iter()
+            .map(|x| parse_parameter(x).unwrap())
+            .collect::<Parameters>();
+        let q = parse_sql(
+            "SELECT :ns FROM minikube WHERE pod.metadata.name = $1",
+            &parameters,
+        );
+        assert_eq!(q.namespaces, vec!["kube-system"]);
+        assert!(q.queries[0].matches(&Record {
+            context: "minikube",
+            namespace: "kube-system",
+            kind: "pod",
+            object: &json!({"metadata": {"name": "a"}}),
+        }));
+    }
 }
@@ -17,97 +17,849 @@
 // IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
 // CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
-use sqlparser::ast::{BinaryOperator, Expr, Ident, Value};
+use crate::evaluator;
+use crate::evaluator::Record;
+use crate::function;
+use crate::function::{Aggregate, Function};
+use crate::parser::ResourceType;
+use crate::value;
+use sqlparser::ast;
+use sqlparser::ast::{BinaryOperator, DataType, Expr, FunctionArg, Ident, UnaryOperator, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
 
 #[derive(Debug, Clone)]
 pub struct Query {
     pub key: Option<BinaryOperator>,
     pub kind: String,
-    pub field1: String,
-    pub field2: String,
-    pub eq: String,
-    pub op: BinaryOperator,
+    pub predicate: Expression,
+}
+
+/// A column given in SELECT statement, i.e. 'LOWER(pod.metadata.name) AS name'
+#[derive(Debug, Clone)]
+pub struct Projection {
+    pub name: String,
+    pub expr: Expression,
+
+    /// Whether the column is expanded to one row per element: i.e. 'UNNEST(pod.spec.containers)'
+    pub unnest: bool,
+}
+
+/// An expression given in ORDER BY statement
+#[derive(Debug, Clone)]
+pub struct OrderBy {
+    pub expr: Expression,
+    pub asc: bool,
+}
+
+/// A column that is not a field of the object, but populated from where the object comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VirtualColumn {
+    /// The kubeconfig context, i.e. 'minikube'
+    Context,
+    Namespace,
+    /// The kind given in the query, i.e. 'pod'
+    Kind,
+    /// The API version of the object, i.e. 'apps/v1'
+    ApiVersion,
+    /// Elapsed time since the creation of the object
+    Age,
+}
+
+/// A segment of a field path: i.e. 'containers', '[0]' or '[*]'
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Field(String),
+    /// Element of an array, negative values are counted from the end
+    Index(i64),
+    /// All elements of an array, or all values of an object
+    Wildcard,
+}
+
+/// The values of the placeholders, by their names: i.e. '1' for '$1' and 'name' for ':name'
+pub type Parameters = HashMap<String, value::Value>;
+
+/// A typed expression that can be evaluated against an object
+#[derive(Debug, Clone)]
+pub enum Expression {
+    /// A field path of an object, optionally prefixed with its kind: i.e. 'pod.status.phase'
+    Column {
+        kind: Option<String>,
+        path: Vec<Segment>,
+    },
+    Virtual(VirtualColumn),
+    Literal(value::Value),
+    Function {
+        function: &'static Function,
+        args: Vec<Expression>,
+    },
+    /// An aggregate function over the rows of a group: i.e. 'COUNT(*)'. The argument is `None`
+    /// for the wildcard.
+    Aggregate {
+        aggregate: Aggregate,
+        arg: Option<Box<Expression>>,
+        /// Whether the duplicate values are ignored: i.e. 'COUNT(DISTINCT pod.spec.nodeName)'
+        distinct: bool,
+    },
+    BinaryOp {
+        left: Box<Expression>,
+        op: BinaryOperator,
+        right: Box<Expression>,
+    },
+    /// Compares with each element of the list: i.e. 'nginx' = ANY(pod.spec.containers[*].image)
+    Any {
+        expr: Box<Expression>,
+        op: BinaryOperator,
+        list: Box<Expression>,
+    },
+    Not(Box<Expression>),
+    IsNull {
+        expr: Box<Expression>,
+        negated: bool,
+    },
+    /// A placeholder that is bound to a value before the evaluation: i.e. '$1' or ':name'
+    Parameter(String),
 }
 
 #[derive(Debug)]
 pub enum Object {
-    Strings(Vec<String>),
-    String(String),
     Query(Query),
     Queries(Vec<Query>),
+    /// A condition that does not refer to any kind: i.e. '_namespace = 'default''
+    Expression(Expression),
+}
+
+impl Query {
+    /// Returns the '--field-selector' equivalent of the predicate, if the API server supports it.
+    /// Only the conditions that are joined with AND at the top level are taken into account.
+    pub fn field_selector(&self) -> Option<String> {
+        let kind = ResourceType::from_str(&self.kind).ok()?;
+
+        let selectors = self
+            .predicate
+            .conjuncts()
+            .into_iter()
+            .filter_map(|x| match x {
+                Expression::BinaryOp { left, op, right } => match (left.as_ref(), right.as_ref()) {
+                    (
+                        Expression::Column {
+                            kind: Some(k),
+                            path,
+                        },
+                        Expression::Literal(value::Value::String(s)),
+                    ) if *k == self.kind => {
+                        let field = path
+                            .iter()
+                            .map(|x| match x {
+                                Segment::Field(f) => Some(f.as_str()),
+                                _ => None,
+                            })
+                            .collect::<Option<Vec<&str>>>()?
+                            .join(".");
+                        if !kind.field_selectors().contains(&field.as_str()) {
+                            return None;
+                        }
+                        match op {
+                            BinaryOperator::Eq => Some(format!("{}={}", field, s)),
+                            BinaryOperator::NotEq => Some(format!("{}!={}", field, s)),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+
+        match selectors.is_empty() {
+            true => None,
+            false => Some(selectors.join(",")),
+        }
+    }
+
+    /// Returns whether the given record satisfies the predicate
+    pub fn matches(&self, record: &Record) -> bool {
+        evaluator::evaluate(&self.predicate, record).is_true()
+    }
+}
+
+impl Expression {
+    /// Returns the first kind referenced by the expression, if any
+    pub fn kind(&self) -> Option<String> {
+        match self {
+            Expression::Column { kind, .. } => kind.clone(),
+            Expression::Virtual(_) | Expression::Literal(_) => None,
+            Expression::Function { args, .. } => args.iter().find_map(|x| x.kind()),
+            Expression::Aggregate { arg, .. } => arg.as_ref().and_then(|x| x.kind()),
+            Expression::BinaryOp { left, right, .. } => left.kind().or_else(|| right.kind()),
+            Expression::Any { expr, list, .. } => expr.kind().or_else(|| list.kind()),
+            Expression::Not(e) => e.kind(),
+            Expression::IsNull { expr, .. } => expr.kind(),
+            Expression::Parameter(_) => None,
+        }
+    }
+
+    /// Returns whether the expression contains any aggregate function
+    pub fn is_aggregate(&self) -> bool {
+        match self {
+            Expression::Aggregate { .. } => true,
+            Expression::Column { .. }
+            | Expression::Virtual(_)
+            | Expression::Literal(_)
+            | Expression::Parameter(_) => false,
+            Expression::Function { args, .. } => args.iter().any(|x| x.is_aggregate()),
+            Expression::BinaryOp { left, right, .. } => left.is_aggregate() || right.is_aggregate(),
+            Expression::Any { expr, list, .. } => expr.is_aggregate() || list.is_aggregate(),
+            Expression::Not(e) => e.is_aggregate(),
+            Expression::IsNull { expr, .. } => expr.is_aggregate(),
+        }
+    }
+
+    /// Returns the conditions that are joined with AND: i.e. 'a AND (b AND c)' is [a, b, c]
+    fn conjuncts(&self) -> Vec<&Expression> {
+        match self {
+            Expression::BinaryOp {
+                left,
+                op: BinaryOperator::And,
+                right,
+            } => {
+                let mut v = left.conjuncts();
+                v.extend(right.conjuncts());
+                v
+            }
+            e => vec![e],
+        }
+    }
+}
+
+impl VirtualColumn {
+    /// Returns all the virtual columns
+    pub fn all() -> &'static [VirtualColumn] {
+        &[
+            VirtualColumn::Context,
+            VirtualColumn::Namespace,
+            VirtualColumn::Kind,
+            VirtualColumn::ApiVersion,
+            VirtualColumn::Age,
+        ]
+    }
+}
+
+impl FromStr for VirtualColumn {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<VirtualColumn, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "_context" => Ok(VirtualColumn::Context),
+            "_namespace" => Ok(VirtualColumn::Namespace),
+            "_kind" => Ok(VirtualColumn::Kind),
+            "_apiversion" => Ok(VirtualColumn::ApiVersion),
+            "_age" => Ok(VirtualColumn::Age),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for VirtualColumn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VirtualColumn::Context => write!(f, "_context"),
+            VirtualColumn::Namespace => write!(f, "_namespace"),
+            VirtualColumn::Kind => write!(f, "_kind"),
+            VirtualColumn::ApiVersion => write!(f, "_apiVersion"),
+            VirtualColumn::Age => write!(f, "_age"),
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Column { kind, path } => {
+                let path = path.iter().map(|x| x.to_string()).collect::<String>();
+                match kind {
+                    Some(k) => write!(f, "{}{}", k, path),
+                    None => write!(f, "{}", path.trim_start_matches('.')),
+                }
+            }
+            Expression::Virtual(v) => write!(f, "{}", v),
+            Expression::Literal(value::Value::String(s)) => write!(f, "'{}'", s),
+            Expression::Literal(value::Value::Null) => write!(f, "NULL"),
+            Expression::Literal(v @ value::Value::Timestamp(_)) => write!(f, "TIMESTAMP '{}'", v),
+            Expression::Literal(v @ value::Value::Duration(_)) => write!(f, "INTERVAL '{}'", v),
+            Expression::Literal(v) => write!(f, "{}", v),
+            Expression::Function { function, args } => write!(
+                f,
+                "{}({})",
+                function.name,
+                args.iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Expression::Aggregate {
+                aggregate,
+                arg,
+                distinct,
+            } => match arg {
+                Some(a) if *distinct => write!(f, "{}(DISTINCT {})", aggregate, a),
+                Some(a) => write!(f, "{}({})", aggregate, a),
+                None => write!(f, "{}(*)", aggregate),
+            },
+            Expression::BinaryOp { left, op, right } => write!(f, "{} {} {}", left, op, right),
+            Expression::Any { expr, op, list } => write!(f, "{} {} ANY({})", expr, op, list),
+            Expression::Not(e) => write!(f, "NOT {}", e),
+            Expression::IsNull { expr, negated } => {
+                write!(f, "{} IS {}NULL", expr, if *negated { "NOT " } else { "" })
+            }
+            Expression::Parameter(p) if p.chars().all(|c| c.is_ascii_digit()) => {
+                write!(f, "${}", p)
+            }
+            Expression::Parameter(p) => write!(f, ":{}", p),
+        }
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Field(x) => write!(f, ".{}", x),
+            Segment::Index(i) => write!(f, "[{}]", i),
+            Segment::Wildcard => write!(f, "[*]"),
+        }
+    }
 }
 
 pub(crate) fn plan_expr(expr: Expr) -> Object {
     match expr {
+        Expr::BinaryOp { left, op, right }
+            if op == BinaryOperator::And || op == BinaryOperator::Or =>
+        {
+            plan_expr_binary_op(*left, op, *right)
+        }
+        Expr::Nested(e) => plan_expr(*e),
+        e => plan_expr_query(e),
+    }
+}
+
+/// Plans the given expression as a typed scalar expression
+pub(crate) fn plan_scalar(expr: Expr) -> Expression {
+    match expr {
+        Expr::Identifier(i) if parameter(&i).is_some() => {
+            Expression::Parameter(parameter(&i).unwrap())
+        }
+        Expr::Identifier(i) => plan_expr_compound_ident(vec![i]),
         Expr::CompoundIdentifier(i) => plan_expr_compound_ident(i),
-        Expr::BinaryOp { left, op, right } => plan_expr_binary_op(*left, op, *right),
-        Expr::Value(v) => plan_expr_value(v),
-        _ => {
-            panic!("unspport");
+        Expr::Value(v) => Expression::Literal(plan_expr_value(v)),
+        Expr::Function(f) => plan_expr_function(f),
+        Expr::Substring {
+            expr,
+            substring_from,
+            substring_for,
+        } => {
+            let mut args = vec![plan_scalar(*expr)];
+            match (substring_from, substring_for) {
+                (from, Some(count)) => {
+                    args.push(
+                        from.map_or(Expression::Literal(value::Value::Integer(1)), |x| {
+                            plan_scalar(*x)
+                        }),
+                    );
+                    args.push(plan_scalar(*count));
+                }
+                (Some(from), None) => args.push(plan_scalar(*from)),
+                (None, None) => {}
+            }
+            plan_function(function::lookup("SUBSTRING").unwrap(), args)
+        }
+        Expr::BinaryOp { left, op, right } => match op {
+            BinaryOperator::Eq
+            | BinaryOperator::NotEq
+            | BinaryOperator::Gt
+            | BinaryOperator::Lt
+            | BinaryOperator::GtEq
+            | BinaryOperator::LtEq
+            | BinaryOperator::And
+            | BinaryOperator::Or
+            | BinaryOperator::Plus
+            | BinaryOperator::Minus
+            | BinaryOperator::Multiply
+            | BinaryOperator::Divide
+            | BinaryOperator::Modulus
+            | BinaryOperator::StringConcat => match (*left, *right) {
+                (l, Expr::Function(f)) if is_function(&f, "ANY") => plan_expr_any(l, op, f),
+                (Expr::Function(f), r) if is_function(&f, "ANY") => {
+                    plan_expr_any(r, flip_operator(op), f)
+                }
+                (l, r) => Expression::BinaryOp {
+                    left: Box::new(plan_scalar(l)),
+                    op,
+                    right: Box::new(plan_scalar(r)),
+                },
+            },
+            _ => panic!("Unsupported operator: {}", op),
+        },
+        Expr::UnaryOp {
+            op: UnaryOperator::Not,
+            expr,
+        } => Expression::Not(Box::new(plan_scalar(*expr))),
+        Expr::UnaryOp {
+            op: UnaryOperator::Plus,
+            expr,
+        } => plan_scalar(*expr),
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => match plan_scalar(*expr) {
+            Expression::Literal(value::Value::Integer(i)) => {
+                Expression::Literal(value::Value::Integer(-i))
+            }
+            Expression::Literal(value::Value::Float(x)) => {
+                Expression::Literal(value::Value::Float(-x))
+            }
+            e => Expression::BinaryOp {
+                left: Box::new(Expression::Literal(value::Value::Integer(0))),
+                op: BinaryOperator::Minus,
+                right: Box::new(e),
+            },
+        },
+        Expr::TypedString { data_type, value } => match data_type {
+            DataType::Timestamp | DataType::Date => Expression::Literal(
+                value::parse_timestamp(&value)
+                    .map(value::Value::Timestamp)
+                    .unwrap_or_else(|| panic!("Unable to parse timestamp: '{}'", value)),
+            ),
+            _ => panic!("Unsupported typed string: {} '{}'", data_type, value),
+        },
+        Expr::IsNull(e) => Expression::IsNull {
+            expr: Box::new(plan_scalar(*e)),
+            negated: false,
+        },
+        Expr::IsNotNull(e) => Expression::IsNull {
+            expr: Box::new(plan_scalar(*e)),
+            negated: true,
+        },
+        Expr::Nested(e) => plan_scalar(*e),
+        e => {
+            panic!("Unsupported expression: {}", e);
         }
     }
 }
 
-fn plan_expr_compound_ident(idents: Vec<Ident>) -> Object {
-    Object::Strings(idents.iter().cloned().map(|e| e.value).collect())
+/// Returns the name of the placeholder, which is given as a quoted identifier by the parser:
+/// i.e. '"$1"' or '":name"'
+pub(crate) fn parameter(ident: &Ident) -> Option<String> {
+    match ident.quote_style {
+        Some('"') => ident
+            .value
+            .strip_prefix('$')
+            .or_else(|| ident.value.strip_prefix(':'))
+            .map(|x| x.to_string()),
+        _ => None,
+    }
 }
 
-fn plan_expr_binary_op(left: Expr, op: BinaryOperator, right: Expr) -> Object {
-    let l = plan_expr(left);
-    let r = plan_expr(right);
+/// Replaces the placeholders of the given expression with their values, as typed literals
+pub(crate) fn bind(expr: Expression, parameters: &Parameters) -> Expression {
+    let bind_box = |e: Box<Expression>| Box::new(bind(*e, parameters));
+    match expr {
+        Expression::Parameter(p) => match parameters.get(&p) {
+            Some(v) => Expression::Literal(v.clone()),
+            None => panic!(
+                "No value given for {}: i.e. '--param {}=value'",
+                Expression::Parameter(p.clone()),
+                p
+            ),
+        },
+        Expression::Function { function, args } => Expression::Function {
+            function,
+            args: args.into_iter().map(|x| bind(x, parameters)).collect(),
+        },
+        Expression::Aggregate {
+            aggregate,
+            arg,
+            distinct,
+        } => Expression::Aggregate {
+            aggregate,
+            arg: arg.map(bind_box),
+            distinct,
+        },
+        Expression::BinaryOp { left, op, right } => Expression::BinaryOp {
+            left: bind_box(left),
+            op,
+            right: bind_box(right),
+        },
+        Expression::Any { expr, op, list } => Expression::Any {
+            expr: bind_box(expr),
+            op,
+            list: bind_box(list),
+        },
+        Expression::Not(e) => Expression::Not(bind_box(e)),
+        Expression::IsNull { expr, negated } => Expression::IsNull {
+            expr: bind_box(expr),
+            negated,
+        },
+        e @ (Expression::Column { .. } | Expression::Virtual(_) | Expression::Literal(_)) => e,
+    }
+}
 
-    match (l, r) {
-        (Object::Strings(a), Object::String(b)) => plan_expr_binary_op_query(a, b, op),
-        (Object::Query(a), Object::Query(b)) => plan_expr_binary_op_query_vec(a, b, op),
-        (Object::Queries(a), Object::Query(b)) => plan_expr_binary_op_query_vec_append(a, b, op),
-        (x, y) => {
-            panic!("Type mismatch L: {:?}, R: {:?}!", x, y)
+/// Plans the given SELECT item, expanding it to one row per element if it is wrapped with UNNEST
+pub(crate) fn plan_projection(expr: Expr, alias: Option<String>) -> Projection {
+    let (expr, unnest) = match expr {
+        Expr::Function(f) if is_function(&f, "UNNEST") => {
+            if f.args.len() != 1 {
+                panic!("UNNEST does only support a single argument: i.e. 'UNNEST(pod.spec.containers)'")
+            }
+            match f.args.into_iter().next().unwrap() {
+                FunctionArg::Unnamed(e) => (plan_scalar(e), true),
+                FunctionArg::Named { name, .. } => {
+                    panic!("UNNEST does not support named argument: {}", name)
+                }
+            }
         }
+        e => (plan_scalar(e), false),
+    };
+
+    Projection {
+        name: alias.unwrap_or_else(|| match unnest {
+            true => format!("UNNEST({})", expr),
+            false => expr.to_string(),
+        }),
+        expr,
+        unnest,
     }
 }
 
-fn plan_expr_value(value: Value) -> Object {
-    match value {
-        Value::SingleQuotedString(s) | Value::DoubleQuotedString(s) => Object::String(s),
-        _ => {
-            panic!("plan_expr_value::unspported!")
-        }
+fn is_function(f: &ast::Function, name: &str) -> bool {
+    f.name.to_string().eq_ignore_ascii_case(name)
+}
+
+/// Returns the operator that gives the same result when the operands are swapped
+fn flip_operator(op: BinaryOperator) -> BinaryOperator {
+    match op {
+        BinaryOperator::Gt => BinaryOperator::Lt,
+        BinaryOperator::Lt => BinaryOperator::Gt,
+        BinaryOperator::GtEq => BinaryOperator::LtEq,
+        BinaryOperator::LtEq => BinaryOperator::GtEq,
+        op => op,
     }
 }
 
-fn plan_expr_binary_op_query(input: Vec<String>, eq: String, op: BinaryOperator) -> Object {
-    if input.len() != 3 {
-        panic!("WHERE statement does only support three length CompoundIdentifier: i.e. 'pod.status.phase'")
+fn plan_expr_any(expr: Expr, op: BinaryOperator, f: ast::Function) -> Expression {
+    if f.args.len() != 1 {
+        panic!("ANY does only support a single argument: i.e. 'ANY(pod.spec.containers[*].image)'")
     }
 
-    Object::Query {
-        0: Query {
-            key: None,
-            kind: input.get(0).unwrap().to_string(),
-            field1: input.get(1).unwrap().to_string(),
-            field2: input.get(2).unwrap().to_string(),
-            eq: eq.replace("_", "-"),
+    match f.args.into_iter().next().unwrap() {
+        FunctionArg::Unnamed(list) => Expression::Any {
+            expr: Box::new(plan_scalar(expr)),
             op,
+            list: Box::new(plan_scalar(list)),
         },
+        FunctionArg::Named { name, .. } => panic!("ANY does not support named argument: {}", name),
     }
 }
 
-fn plan_expr_binary_op_query_vec(input: Query, mut eq: Query, op: BinaryOperator) -> Object {
-    let mut v = vec![input];
-    eq.key = Some(op);
-    v.push(eq);
+fn plan_expr_query(expr: Expr) -> Object {
+    let predicate = plan_scalar(expr);
+    if predicate.is_aggregate() {
+        panic!(
+            "WHERE statement does not support aggregate functions, got: {}",
+            predicate
+        )
+    }
 
-    Object::Queries(v)
+    match predicate.kind() {
+        Some(kind) => Object::Query(Query {
+            key: None,
+            kind,
+            predicate,
+        }),
+        None => Object::Expression(predicate),
+    }
 }
 
-fn plan_expr_binary_op_query_vec_append(
-    input: Vec<Query>,
-    mut eq: Query,
-    op: BinaryOperator,
-) -> Object {
+/// Joins the condition that does not refer to any kind into the predicate of the query
+fn plan_expr_join_expression(mut q: Query, op: BinaryOperator, e: Expression) -> Query {
+    q.predicate = Expression::BinaryOp {
+        left: Box::new(q.predicate),
+        op,
+        right: Box::new(e),
+    };
+    q
+}
+
+fn plan_expr_compound_ident(idents: Vec<Ident>) -> Expression {
+    let mut path = idents
+        .into_iter()
+        .map(plan_expr_segment)
+        .collect::<Vec<Segment>>();
+
+    match path.first() {
+        Some(Segment::Field(v)) if path.len() == 1 && VirtualColumn::from_str(v).is_ok() => {
+            Expression::Virtual(VirtualColumn::from_str(v).unwrap())
+        }
+        Some(Segment::Field(k))
+            if path.len() > 1 && ResourceType::from_str(&k.to_lowercase()).is_ok() =>
+        {
+            let kind = k.to_lowercase();
+            path.remove(0);
+            Expression::Column {
+                kind: Some(kind),
+                path,
+            }
+        }
+        _ => Expression::Column { kind: None, path },
+    }
+}
+
+/// Array accessors are given as quoted identifiers by the parser: i.e. '"[0]"' or '"[*]"'
+fn plan_expr_segment(ident: Ident) -> Segment {
+    let accessor = match ident.quote_style {
+        Some('"') => ident
+            .value
+            .strip_prefix('[')
+            .and_then(|x| x.strip_suffix(']')),
+        _ => None,
+    };
+
+    match accessor {
+        Some("*") => Segment::Wildcard,
+        Some(i) => Segment::Index(
+            i.trim()
+                .parse::<i64>()
+                .unwrap_or_else(|_| panic!("Unsupported array index: [{}]", i)),
+        ),
+        None => Segment::Field(ident.value),
+    }
+}
+
+fn plan_expr_function(f: ast::Function) -> Expression {
+    if f.over.is_some() {
+        panic!("Function {} does not support OVER!", f.name)
+    }
+
+    let name = f.name.to_string();
+    if let Some(aggregate) = function::lookup_aggregate(&name) {
+        return plan_expr_aggregate(aggregate, f);
+    }
+
+    if f.distinct {
+        panic!("Function {} does not support DISTINCT!", f.name)
+    }
+
+    match name.to_uppercase().as_str() {
+        "ANY" => panic!("ANY is only supported as a comparison operand: i.e. 'x = ANY(list)'"),
+        "UNNEST" => {
+            panic!("UNNEST is only supported as a SELECT column: i.e. 'SELECT UNNEST(list)'")
+        }
+        _ => {}
+    }
+
+    let function =
+        function::lookup(&name).unwrap_or_else(|| panic!("Unsupported function: {}", name));
+
+    let args = f
+        .args
+        .into_iter()
+        .map(|a| match a {
+            FunctionArg::Unnamed(e) => plan_scalar(e),
+            FunctionArg::Named { name, .. } => {
+                panic!(
+                    "Function {} does not support named argument: {}",
+                    function.name, name
+                )
+            }
+        })
+        .collect::<Vec<Expression>>();
+
+    plan_function(function, args)
+}
+
+fn plan_expr_aggregate(aggregate: Aggregate, f: ast::Function) -> Expression {
+    if f.args.len() != 1 {
+        panic!("Function {} does only support a single argument!", f.name)
+    }
+
+    let arg = match f.args.into_iter().next().unwrap() {
+        FunctionArg::Unnamed(Expr::Wildcard) if aggregate == Aggregate::Count && !f.distinct => {
+            None
+        }
+        FunctionArg::Unnamed(e) => Some(plan_scalar(e)),
+        FunctionArg::Named { name, .. } => {
+            panic!(
+                "Function {} does not support named argument: {}",
+                aggregate, name
+            )
+        }
+    };
+    if arg.as_ref().is_some_and(|x| x.is_aggregate()) {
+        panic!(
+            "Function {} does not support nested aggregate functions!",
+            aggregate
+        )
+    }
+
+    Expression::Aggregate {
+        aggregate,
+        arg: arg.map(Box::new),
+        distinct: f.distinct,
+    }
+}
+
+fn plan_function(function: &'static Function, args: Vec<Expression>) -> Expression {
+    if !function.accepts(args.len()) {
+        panic!(
+            "Function {} does not accept {} argument(s)!",
+            function.name,
+            args.len()
+        )
+    }
+
+    Expression::Function { function, args }
+}
+
+fn plan_expr_value(value: Value) -> value::Value {
+    match value {
+        Value::SingleQuotedString(s) | Value::DoubleQuotedString(s) => value::Value::String(s),
+        Value::Number(n, _) => match n.parse::<i64>() {
+            Ok(i) => value::Value::Integer(i),
+            Err(_) => value::Value::Float(
+                n.parse::<f64>()
+                    .unwrap_or_else(|_| panic!("Unable to parse number: {}", n)),
+            ),
+        },
+        Value::Boolean(b) => value::Value::Bool(b),
+        Value::Null => value::Value::Null,
+        Value::Interval {
+            value,
+            leading_field,
+            last_field: None,
+            ..
+        } => {
+            let unit = leading_field.map_or("second".to_string(), |x| x.to_string());
+            value::parse_duration(&value, &unit)
+                .map(value::Value::Duration)
+                .unwrap_or_else(|| panic!("Unable to parse interval: '{}'", value))
+        }
+        v => {
+            panic!("Unsupported value: {}", v)
+        }
+    }
+}
+
+fn plan_expr_binary_op(left: Expr, op: BinaryOperator, right: Expr) -> Object {
+    let l = plan_expr(left);
+    let r = plan_expr(right);
+
+    match (l, r) {
+        (Object::Query(a), Object::Query(b)) => plan_expr_binary_op_query_vec(vec![a], vec![b], op),
+        (Object::Queries(a), Object::Query(b)) => plan_expr_binary_op_query_vec(a, vec![b], op),
+        (Object::Query(a), Object::Queries(b)) => plan_expr_binary_op_query_vec(vec![a], b, op),
+        (Object::Queries(a), Object::Queries(b)) => plan_expr_binary_op_query_vec(a, b, op),
+        (Object::Query(a), Object::Expression(b)) | (Object::Expression(b), Object::Query(a)) => {
+            Object::Query(plan_expr_join_expression(a, op, b))
+        }
+        (Object::Queries(a), Object::Expression(b))
+        | (Object::Expression(b), Object::Queries(a))
+            if op == BinaryOperator::And =>
+        {
+            Object::Queries(
+                a.into_iter()
+                    .map(|q| plan_expr_join_expression(q, BinaryOperator::And, b.clone()))
+                    .collect(),
+            )
+        }
+        (Object::Expression(a), Object::Expression(b)) => {
+            Object::Expression(Expression::BinaryOp {
+                left: Box::new(a),
+                op,
+                right: Box::new(b),
+            })
+        }
+        (x, y) => {
+            panic!("Type mismatch L: {:?}, R: {:?}!", x, y)
+        }
+    }
+}
+
+/// Joins the queries with the given operator. The conditions on the same kind are joined into a
+/// single predicate if all the queries are joined with the same operator, so that an object is
+/// only listed once: i.e. 'pod.a = 1 AND pod.b = 2'. Otherwise they are kept as separate queries
+/// in order not to lose their grouping: i.e. '(pod.a = 1 AND deployment.b = 2) OR pod.c = 3'
+fn plan_expr_binary_op_query_vec(input: Vec<Query>, eq: Vec<Query>, op: BinaryOperator) -> Object {
+    let same_op = input
+        .iter()
+        .chain(eq.iter())
+        .all(|x| x.key.as_ref().is_none_or(|k| *k == op));
+
     let mut v = input;
-    eq.key = Some(op);
-    v.push(eq);
+    for mut q in eq {
+        match v.iter_mut().find(|x| same_op && x.kind == q.kind) {
+            Some(existing) => {
+                existing.predicate = Expression::BinaryOp {
+                    left: Box::new(existing.predicate.clone()),
+                    op: op.clone(),
+                    right: Box::new(q.predicate),
+                }
+            }
+            None => {
+                q.key = q.key.or_else(|| Some(op.clone()));
+                v.push(q);
+            }
+        }
+    }
+
+    match v.len() {
+        1 => Object::Query(v.remove(0)),
+        _ => Object::Queries(v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser;
 
-    Object::Queries(v)
+    /// Returns the kinds of the queries of the given WHERE statement, along with their operators
+    fn queries(condition: &str) -> Vec<(String, Option<String>)> {
+        let sql = format!("SELECT default FROM minikube WHERE {}", condition);
+        parser::parse_sql(&sql, &Default::default())
+            .queries
+            .into_iter()
+            .map(|q| (q.kind, q.key.map(|x| x.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn conditions_under_the_same_operator_are_merged() {
+        assert_eq!(
+            queries("pod.metadata.name = 'a' OR (deployment.spec.replicas = 0 OR pod.metadata.name = 'b')"),
+            vec![
+                ("pod".to_string(), None),
+                ("deployment".to_string(), Some("OR".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn conditions_under_other_operators_are_kept_apart() {
+        assert_eq!(
+            queries("(pod.metadata.name = 'a' AND deployment.spec.replicas = 0) OR pod.metadata.name = 'b'"),
+            vec![
+                ("pod".to_string(), None),
+                ("deployment".to_string(), Some("AND".to_string())),
+                ("pod".to_string(), Some("OR".to_string())),
+            ]
+        );
+        assert_eq!(
+            queries("pod.metadata.name = 'b' OR (pod.metadata.name = 'a' AND deployment.spec.replicas = 0)"),
+            vec![
+                ("pod".to_string(), None),
+                ("pod".to_string(), Some("OR".to_string())),
+                ("deployment".to_string(), Some("AND".to_string())),
+            ]
+        );
+    }
 }
@@ -17,43 +17,164 @@
 // IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
 // CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
-use crate::parser::ResourceType;
-use crate::planner::Query;
-use k8s_openapi::api::apps::v1::Deployment;
-use k8s_openapi::api::core::v1::Pod;
-use kube::api::ObjectList;
-use kube::Resource;
-use prettytable::{Cell, Row, Table};
-use std::collections::HashMap;
-
-#[derive(Debug)]
-pub struct PrintItem<'a> {
-    pub context: &'a str,
-    pub namespace: &'a str,
-    pub kind: ResourceType,
-    pub value: String,
+use crate::config::Config;
+use crate::evaluator;
+use crate::jsonpath;
+use crate::jsonpath::Template;
+use crate::planner::Segment;
+use crate::planner::{Expression, Projection, Query};
+use crate::result;
+use crate::value::Value;
+use prettytable::format::{FormatBuilder, TableFormat};
+use prettytable::{color, Attr, Cell, Row, Table};
+use std::io::Write;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// The first of the private use characters that stand in for the styled text of the nested
+/// tables, since the widths of their cells would count the escape codes otherwise
+const MARKER: u32 = 0xF0000;
+
+/// The style of the marked text of a kind, beside the colours of the statuses
+const BOLD: u32 = 16;
+
+/// The style of the marked text of a context header, highlighted to tell the clusters apart
+const HEADER: u32 = 17;
+
+/// The page of the HTML output, where '{header}' and '{body}' are replaced with the rows
+const HTML_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>kubesql</title>
+  <style>
+    body { font-family: sans-serif; }
+    table { border-collapse: collapse; }
+    th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+    th { background: #f0f0f0; cursor: pointer; user-select: none; }
+    th[data-order="asc"]::after { content: " \25B2"; }
+    th[data-order="desc"]::after { content: " \25BC"; }
+  </style>
+</head>
+<body>
+  <table>
+    <thead>
+      <tr>{header}</tr>
+    </thead>
+    <tbody>
+{body}    </tbody>
+  </table>
+  <script>
+    document.querySelectorAll("th").forEach(function (th, i) {
+      th.addEventListener("click", function () {
+        var body = th.closest("table").tBodies[0];
+        var asc = th.dataset.order !== "asc";
+        th.parentNode.querySelectorAll("th").forEach(function (x) { delete x.dataset.order; });
+        th.dataset.order = asc ? "asc" : "desc";
+        Array.from(body.rows)
+          .sort(function (a, b) {
+            var c = a.cells[i].textContent.localeCompare(b.cells[i].textContent, undefined, { numeric: true });
+            return asc ? c : -c;
+          })
+          .forEach(function (r) { body.appendChild(r); });
+      });
+    });
+  </script>
+</body>
+</html>
+"#;
+
+/// The supported output formats
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Format {
+    /// A flat table of a row per object, truncated to the width of the terminal
+    Table,
+    /// A flat table of a row per object, as wide as needed
+    Wide,
+    /// Tables nested by kind, context and namespace
+    #[default]
+    Nested,
+    /// A JSON array of the rows
+    Json,
+    /// A JSON object per line, per row
+    Ndjson,
+    /// Comma-separated values, quoted as per RFC 4180
+    Csv,
+    /// Tab-separated values, quoted like CSV
+    Tsv,
+    /// A YAML document per object, as returned from the API server
+    Yaml,
+    /// A GitHub-flavoured Markdown table
+    Markdown,
+    /// A self-contained HTML page of a sortable table
+    Html,
+    /// The given template per object: i.e. 'jsonpath={.metadata.name}{"\n"}'
+    JsonPath(Template),
+    /// A row per object of the given columns: i.e. 'custom-columns=NAME:.metadata.name'
+    CustomColumns(Vec<(String, Vec<Segment>)>),
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Format, Self::Err> {
+        if let Some(template) = input.strip_prefix("jsonpath=") {
+            return Ok(Format::JsonPath(Template::parse(template)?));
+        }
+        if let Some(spec) = input.strip_prefix("custom-columns=") {
+            return spec
+                .split(',')
+                .map(|column| match column.split_once(':') {
+                    Some((name, path)) => Ok((name.to_string(), jsonpath::parse_path(path)?)),
+                    None => Err(format!(
+                        "Expected NAME:PATH in custom-columns, found '{}'",
+                        column
+                    )),
+                })
+                .collect::<Result<Vec<(String, Vec<Segment>)>, String>>()
+                .map(Format::CustomColumns);
+        }
+
+        match input {
+            "table" => Ok(Format::Table),
+            "wide" => Ok(Format::Wide),
+            "nested" => Ok(Format::Nested),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "yaml" => Ok(Format::Yaml),
+            "markdown" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            _ => Err(format!(
+                "Unsupported output format '{}', expected one of: {}, jsonpath=TEMPLATE, custom-columns=SPEC",
+                input,
+                Format::names().join(", ")
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// Returns the names of all supported formats, for the command line
+    pub fn names() -> &'static [&'static str] {
+        &[
+            "table", "wide", "nested", "json", "ndjson", "csv", "tsv", "yaml", "markdown", "html",
+        ]
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Printer<'a> {
-    //items: Option<&'a Vec<PrintItem>>,
-    items: Vec<PrintItem<'a>>,
+    format: Format,
+    no_headers: bool,
+    no_managed_fields: bool,
+    color: bool,
+    rows: Vec<result::Row>,
     contexts: Option<&'a [String]>,
     namespaces: Option<&'a [String]>,
     queries: Option<&'a [Query]>,
-    a: Option<&'a String>,
-}
-
-impl<'a> Default for Printer<'a> {
-    fn default() -> Self {
-        Self {
-            items: vec![],
-            contexts: None,
-            namespaces: None,
-            queries: None,
-            a: None,
-        }
-    }
+    projections: Option<&'a [Projection]>,
 }
 
 impl<'a> Printer<'a> {
@@ -83,147 +204,860 @@ impl<'a> Printer<'a> {
         self
     }
 
-    pub fn insert_deployments(
-        &mut self,
-        ctx: &'a str,
-        ns: &'a str,
-        objects: ObjectList<Deployment>,
-    ) {
-        let v = objects
-            .items
-            .into_iter()
-            .map(|x| x.name())
-            .collect::<Vec<String>>();
-        self.items.push(PrintItem {
-            context: ctx,
-            namespace: ns,
-            kind: ResourceType::Deployment,
-            value: v.join("\n"),
-        })
+    /// Set the given projections
+    pub fn projections(mut self, projections: &'a [Projection]) -> Printer<'a> {
+        self.projections = Option::from(projections);
+        self
     }
 
-    pub fn insert_pods(&mut self, ctx: &'a str, ns: &'a str, objects: ObjectList<Pod>) {
-        let v = objects
-            .items
-            .into_iter()
-            .map(|x| x.name())
-            .collect::<Vec<String>>();
-        self.items.push(PrintItem {
-            context: ctx,
-            namespace: ns,
-            kind: ResourceType::Pod,
-            value: v.join("\n"),
-        });
+    /// Set the output format
+    pub fn format(mut self, format: Format) -> Printer<'a> {
+        self.format = format;
+        self
     }
 
-    pub fn print(self) {
-        // 1. Creating tables for all given contexts
+    /// Set whether the header row is omitted, if the format has one
+    pub fn no_headers(mut self, no_headers: bool) -> Printer<'a> {
+        self.no_headers = no_headers;
+        self
+    }
 
-        // Represents 'Context - Table' mapping
-        let mut table_context_pods: HashMap<String, Table> = HashMap::new();
-        let mut table_context_deployments: HashMap<String, Table> = HashMap::new();
+    /// Set whether the 'metadata.managedFields' of the objects are omitted
+    pub fn no_managed_fields(mut self, no_managed_fields: bool) -> Printer<'a> {
+        self.no_managed_fields = no_managed_fields;
+        self
+    }
 
-        let should_append_pod: bool = self
-            .queries
-            .unwrap()
+    /// Set whether the table outputs are colourised
+    pub fn color(mut self, color: bool) -> Printer<'a> {
+        self.color = color;
+        self
+    }
+
+    /// Set the output format and its options of the given configuration
+    pub fn config(self, config: &Config) -> Printer<'a> {
+        self.format(config.format.clone())
+            .no_headers(config.no_headers)
+            .no_managed_fields(config.no_managed_fields)
+            .color(config.color)
+    }
+
+    /// Set the rows of the result set
+    pub fn rows(mut self, rows: Vec<result::Row>) -> Printer<'a> {
+        self.rows = rows;
+        self
+    }
+
+    /// Returns the column names of the rows
+    fn columns(&self) -> Vec<String> {
+        match self.projections {
+            Some(p) if !p.is_empty() => p.iter().map(|x| x.name.clone()).collect(),
+            _ => result::default_projections()
+                .into_iter()
+                .map(|x| x.name)
+                .collect(),
+        }
+    }
+
+    /// Returns the given row as a JSON object, keyed by the column names along with the
+    /// metadata. The metadata of an aggregated row is `null` unless it is shared.
+    fn to_json(row: &result::Row, columns: &[String]) -> serde_json::Value {
+        let metadata = |x: &str| match x.is_empty() {
+            true => serde_json::Value::Null,
+            false => serde_json::Value::String(x.to_string()),
+        };
+
+        let mut object = serde_json::Map::new();
+        object.insert("context".to_string(), metadata(&row.context));
+        object.insert("namespace".to_string(), metadata(&row.namespace));
+        object.insert("kind".to_string(), metadata(&row.kind));
+        for (name, value) in columns.iter().zip(row.values.iter()) {
+            object.insert(name.clone(), serde_json::Value::from(value));
+        }
+
+        serde_json::Value::Object(object)
+    }
+
+    fn print_json(self) {
+        let columns = self.columns();
+        let rows = self
+            .rows
             .iter()
-            .any(|x| x.kind.eq_ignore_ascii_case(&*ResourceType::Pod.to_string()));
-        let should_append_deployment: bool = self.queries.unwrap().iter().any(|x| {
-            x.kind
-                .eq_ignore_ascii_case(&*ResourceType::Deployment.to_string())
-        });
-
-        // 2. Initialize the all contexts
-        for context in self.contexts.unwrap() {
-            let mut table_ctx = Table::new();
-            let cells = self
-                .namespaces
-                .unwrap()
+            .map(|x| Self::to_json(x, &columns))
+            .collect::<Vec<serde_json::Value>>();
+
+        println!("{}", serde_json::to_string_pretty(&rows).unwrap());
+    }
+
+    /// Prints a row per line, flushing each one so that they can be consumed as they arrive
+    fn print_ndjson(self) {
+        let columns = self.columns();
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+
+        for r in &self.rows {
+            writeln!(out, "{}", Self::to_json(r, &columns)).expect("Unable to write the row");
+            out.flush().expect("Unable to write the row");
+        }
+    }
+
+    /// Prints a watch event per row, i.e. `{"type": "ADDED", "object": {...}}`, flushing each one
+    pub fn print_events(self, event: &str) {
+        let columns = self.columns();
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+
+        for r in &self.rows {
+            let event = serde_json::json!({
+                "type": event,
+                "object": Self::to_json(r, &columns),
+            });
+            writeln!(out, "{}", event).expect("Unable to write the event");
+            out.flush().expect("Unable to write the event");
+        }
+    }
+
+    /// Quotes the given field if it contains the delimiter, a quote or a line break, as per
+    /// RFC 4180
+    fn quote(field: &str, delimiter: char) -> String {
+        if field.contains([delimiter, '"', '\r', '\n']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+
+    fn print_delimited(self, delimiter: char) {
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+        let separator = delimiter.to_string();
+
+        let mut write = |fields: Vec<String>| {
+            let line = fields
                 .iter()
-                .map(|x| Cell::new(x))
-                .collect::<Vec<Cell>>();
-            table_ctx.add_row(Row::new(cells));
+                .map(|x| Self::quote(x, delimiter))
+                .collect::<Vec<String>>()
+                .join(&separator);
+            write!(out, "{}\r\n", line).expect("Unable to write the row");
+        };
 
-            let mut table_ctx_pods = table_ctx.clone();
-            let mut table_ctx_deployments = table_ctx.clone();
+        if !self.no_headers {
+            write(self.header());
+        }
 
-            let mut cells_pods: Vec<Cell> = Vec::new();
-            let mut cells_deployments: Vec<Cell> = Vec::new();
+        for r in &self.rows {
+            write(Self::fields(r));
+        }
+    }
 
-            for ns in self.namespaces.unwrap() {
-                if should_append_pod {
-                    let pods = self
-                        .items
-                        .iter()
-                        .filter(|f| {
-                            f.kind == ResourceType::Pod
-                                && *f.context == *context
-                                && *f.namespace == *ns
-                        })
-                        .map(|m| m.value.clone())
-                        .collect::<String>();
-                    if !pods.is_empty() {
-                        cells_pods.push(Cell::new(&*pods));
-                    } else {
-                        cells_pods.push(Cell::new("-"));
-                    }
+    /// Returns the names of the metadata and the columns, for the outputs with a header row
+    fn header(&self) -> Vec<String> {
+        let mut names = vec![
+            "context".to_string(),
+            "namespace".to_string(),
+            "kind".to_string(),
+        ];
+        names.extend(self.columns());
+        names
+    }
+
+    /// Returns the metadata and the values of the given row as text, where NULL is empty
+    fn fields(row: &result::Row) -> Vec<String> {
+        let mut fields = vec![row.context.clone(), row.namespace.clone(), row.kind.clone()];
+        fields.extend(row.values.iter().map(|x| match x.is_null() {
+            true => String::new(),
+            false => x.to_string(),
+        }));
+        fields
+    }
+
+    /// Prints a GitHub-flavoured Markdown table
+    fn print_markdown(self) {
+        let line = |fields: Vec<String>| {
+            let cells = fields
+                .iter()
+                .map(|x| {
+                    x.replace('<', "&lt;")
+                        .replace('>', "&gt;")
+                        .replace('|', "\\|")
+                        .replace('\n', "<br>")
+                })
+                .collect::<Vec<String>>();
+            format!("| {} |", cells.join(" | "))
+        };
+
+        let header = self.header();
+        let separator = format!("|{}|", vec!["---"; header.len()].join("|"));
+        println!("{}", line(header));
+        println!("{}", separator);
+        for r in &self.rows {
+            println!("{}", line(Self::fields(r)));
+        }
+    }
+
+    /// Escapes the given text to be placed in an HTML document
+    fn escape_html(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    /// Prints a self-contained HTML page of a table, which is sorted by clicking the headers
+    fn print_html(self) {
+        let header = self
+            .header()
+            .iter()
+            .map(|x| format!("<th>{}</th>", Self::escape_html(x)))
+            .collect::<String>();
+        let body = self
+            .rows
+            .iter()
+            .map(|r| {
+                let cells = Self::fields(r)
+                    .iter()
+                    .map(|x| format!("<td>{}</td>", Self::escape_html(x)))
+                    .collect::<String>();
+                format!("      <tr>{}</tr>\n", cells)
+            })
+            .collect::<String>();
+
+        print!(
+            "{}",
+            HTML_TEMPLATE
+                .replace("{header}", &header)
+                .replace("{body}", &body)
+        );
+    }
+
+    /// Returns the objects of the rows, once per object even if it is expanded to many rows.
+    /// Aggregated rows are returned as they are in the JSON output.
+    fn objects(&self) -> Vec<serde_json::Value> {
+        let columns = self.columns();
+        let mut printed: Vec<*const serde_json::Value> = vec![];
+        let mut objects = vec![];
+
+        for r in &self.rows {
+            match &r.object {
+                Some(o) if printed.contains(&Rc::as_ptr(o)) => continue,
+                Some(o) => {
+                    printed.push(Rc::as_ptr(o));
+                    objects.push(o.as_ref().clone());
                 }
+                None => objects.push(Self::to_json(r, &columns)),
+            }
+        }
+
+        objects
+    }
+
+    /// Prints the given template for each object, like 'kubectl get -o jsonpath'
+    fn print_jsonpath(&self, template: &Template) {
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+
+        for o in self.objects() {
+            write!(out, "{}", template.render(&o)).expect("Unable to write the object");
+        }
+        out.flush().expect("Unable to write the object");
+    }
+
+    /// Prints a row per object and the given columns, like 'kubectl get -o custom-columns'
+    fn print_custom_columns(&self, columns: &[(String, Vec<Segment>)]) {
+        let mut table = Table::new();
+        table.set_format(Self::plain_format());
+        if !self.no_headers {
+            table.set_titles(Row::new(
+                columns
+                    .iter()
+                    .map(|(x, _)| self.title_cell(x))
+                    .collect::<Vec<Cell>>(),
+            ));
+        }
+
+        for o in self.objects() {
+            table.add_row(Row::new(
+                columns
+                    .iter()
+                    .map(|(_, path)| {
+                        let values = evaluator::select(&o, path)
+                            .into_iter()
+                            .map(jsonpath::text)
+                            .collect::<Vec<String>>();
+                        let color = match Self::is_status(path) {
+                            true => Self::statuses_color(&values),
+                            false => None,
+                        };
+                        match values.is_empty() {
+                            true => self.value_cell("<none>", None),
+                            false => self.value_cell(&values.join(","), color),
+                        }
+                    })
+                    .collect::<Vec<Cell>>(),
+            ));
+        }
+
+        table.printstd();
+    }
+
+    /// Returns the colour of the given phase, status or condition: i.e. 'Running' is green
+    fn status_color(text: &str) -> Option<color::Color> {
+        match text {
+            "Running" | "Succeeded" | "Completed" | "Ready" | "True" => Some(color::GREEN),
+            "Pending" | "ContainerCreating" | "Terminating" | "Unknown" => Some(color::YELLOW),
+            "Failed" | "CrashLoopBackOff" | "Error" | "ImagePullBackOff" | "ErrImagePull"
+            | "OOMKilled" | "Evicted" | "False" => Some(color::RED),
+            _ => None,
+        }
+    }
+
+    /// Returns the most severe colour of the given statuses: i.e. the conditions 'True,False'
+    /// are red
+    fn statuses_color(texts: &[String]) -> Option<color::Color> {
+        let severity = [color::RED, color::YELLOW, color::GREEN];
+        texts
+            .iter()
+            .filter_map(|x| Self::status_color(x))
+            .min_by_key(|c| severity.iter().position(|x| x == c))
+    }
+
+    /// Returns whether the given path is under the status of the objects: i.e. 'status.phase'
+    fn is_status(path: &[Segment]) -> bool {
+        matches!(path.first(), Some(Segment::Field(f)) if f == "status")
+    }
+
+    /// Returns whether each column is a status of the objects, which is coloured by its value:
+    /// i.e. 'pod.status.phase' but not 'pod.metadata.name', even if a pod is named 'Running'
+    fn status_columns(&self) -> Vec<bool> {
+        let projections = match self.projections {
+            Some(p) if !p.is_empty() => p.to_vec(),
+            _ => result::default_projections(),
+        };
+        projections
+            .iter()
+            .map(|p| matches!(&p.expr, Expression::Column { path, .. } if Self::is_status(path)))
+            .collect()
+    }
 
-                if should_append_deployment {
-                    let deployments = self
-                        .items
+    /// Returns the colour of each value of the row, if its column is a status
+    fn value_colors(row: &result::Row, statuses: &[bool]) -> Vec<Option<color::Color>> {
+        row.values
+            .iter()
+            .zip(statuses.iter())
+            .map(|(v, status)| match status {
+                true => Self::statuses_color(
+                    &v.clone()
+                        .into_elements()
                         .iter()
-                        .filter(|f| {
-                            f.kind == ResourceType::Deployment
-                                && *f.context == *context
-                                && *f.namespace == *ns
-                        })
-                        .map(|m| m.value.clone())
-                        .collect::<String>();
-                    if !deployments.is_empty() {
-                        cells_deployments.push(Cell::new(&*deployments));
-                    } else {
-                        cells_deployments.push(Cell::new("-"));
-                    }
+                        .map(Value::to_string)
+                        .collect::<Vec<String>>(),
+                ),
+                false => None,
+            })
+            .collect()
+    }
+
+    /// Returns the cell of a value, in the colour of its status if any
+    fn value_cell(&self, text: &str, color: Option<color::Color>) -> Cell {
+        let cell = Cell::new(text);
+        match color {
+            Some(c) if self.color => cell.with_style(Attr::ForegroundColor(c)),
+            _ => cell,
+        }
+    }
+
+    /// Replaces the given text with private use characters of the same width, in order to style
+    /// it once the nested tables are rendered: i.e. `color::GREEN`, `BOLD` or `HEADER`
+    fn mark(&self, text: &str, style: u32) -> String {
+        match self.color && text.is_ascii() {
+            true => text
+                .chars()
+                .filter_map(|c| char::from_u32(MARKER + (style << 8) + c as u32))
+                .collect(),
+            false => text.to_string(),
+        }
+    }
+
+    /// Replaces the marked text with the escape codes of its style
+    fn unmark(text: &str) -> String {
+        let mut out = String::new();
+        let mut current: Option<u32> = None;
+        for c in text.chars() {
+            let marked = match c as u32 {
+                x if (MARKER..MARKER + (HEADER << 8) + 0x100).contains(&x) => Some(x - MARKER),
+                _ => None,
+            };
+            let style = marked.map(|x| x >> 8);
+            if style != current {
+                if current.is_some() {
+                    out.push_str("\x1b[0m");
                 }
+                match style {
+                    Some(BOLD) => out.push_str("\x1b[1m"),
+                    Some(HEADER) => out.push_str("\x1b[1;36m"),
+                    Some(c) => out.push_str(&format!("\x1b[{}m", 30 + c)),
+                    None => {}
+                }
+                current = style;
             }
+            match marked {
+                Some(x) => out.push((x & 0xFF) as u8 as char),
+                None => out.push(c),
+            }
+        }
+        if current.is_some() {
+            out.push_str("\x1b[0m");
+        }
+        out
+    }
 
-            table_ctx_pods.add_row(Row::new(cells_pods));
-            table_ctx_deployments.add_row(Row::new(cells_deployments));
-
-            table_context_pods.insert(context.clone(), table_ctx_pods);
-            table_context_deployments.insert(context.clone(), table_ctx_deployments);
+    /// Returns the cell of a column title
+    fn title_cell(&self, text: &str) -> Cell {
+        let cell = Cell::new(text);
+        match self.color {
+            true => cell.with_style(Attr::Bold),
+            false => cell,
         }
+    }
+
+    /// Returns the borderless table format of 'kubectl get', where every column is followed by
+    /// 3 spaces
+    pub fn plain_format() -> TableFormat {
+        FormatBuilder::new().padding(0, 3).build()
+    }
+
+    /// Prints the objects of the rows as a multi-document YAML stream, once per object even if
+    /// it is expanded to many rows. Aggregated rows are printed as they are in the JSON output.
+    fn print_yaml(self) {
+        for mut document in self.objects() {
+            if self.no_managed_fields {
+                if let Some(m) = document.get_mut("metadata").and_then(|x| x.as_object_mut()) {
+                    m.remove("managedFields");
+                }
+            }
 
-        let mut row: Vec<Row> = vec![];
+            print!("{}", serde_yaml::to_string(&document).unwrap());
+        }
+    }
 
-        let mut cs = self
-            .contexts
-            .unwrap()
+    /// Returns the cell text of the given row, a line per row, where the statuses are marked
+    /// with their colours
+    fn describe(&self, row: &result::Row, statuses: &[bool]) -> String {
+        row.values
             .iter()
-            .map(|x| Cell::new(x.as_str()))
-            .collect::<Vec<Cell>>();
-        cs.insert(0, Cell::new("KIND / CONTEXT"));
-        row.push(Row::new(cs));
+            .zip(Self::value_colors(row, statuses))
+            .map(|(x, color)| match color {
+                Some(c) => self.mark(&x.to_string(), c),
+                None => x.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
 
-        if should_append_pod {
-            let mut rows_pod: Row = table_context_pods
+    /// Prints the rows that are aggregated from multiple contexts, namespaces or kinds, since
+    /// they do not belong to a single cell of the nested tables
+    fn print_flat(self) {
+        let mut table = Table::new();
+        table.set_titles(Row::new(
+            self.columns()
                 .iter()
-                .map(|x| Cell::from(x.1))
-                .collect::<Row>();
-            rows_pod.insert_cell(0, Cell::new("pod"));
-            row.push(rows_pod);
+                .map(|x| self.title_cell(&x.to_uppercase()))
+                .collect::<Vec<Cell>>(),
+        ));
+
+        let statuses = self.status_columns();
+        for r in &self.rows {
+            table.add_row(Row::new(
+                r.values
+                    .iter()
+                    .zip(Self::value_colors(r, &statuses))
+                    .map(|(x, color)| self.value_cell(&x.to_string(), color))
+                    .collect::<Vec<Cell>>(),
+            ));
         }
 
-        if should_append_deployment {
-            let mut rows_deployment: Row = table_context_deployments
+        table.printstd();
+    }
+
+    /// Shortens the given text to the given number of characters, marking it with an ellipsis
+    fn truncate(text: &str, width: usize) -> String {
+        if text.chars().count() <= width {
+            return text.to_string();
+        }
+        let mut s = text
+            .chars()
+            .take(width.saturating_sub(1))
+            .collect::<String>();
+        s.push('…');
+        s
+    }
+
+    /// Prints a row per object like 'kubectl get', truncating the widest columns until the
+    /// table fits into the terminal if `truncate` is set
+    fn print_wide(self, truncate: bool) {
+        let explicit = matches!(self.projections, Some(p) if !p.is_empty());
+
+        let mut titles = vec!["CONTEXT", "NAMESPACE", "KIND", "NAME"]
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+        if explicit {
+            titles.extend(self.columns().iter().map(|x| x.to_uppercase()));
+        }
+
+        // the colours of the statuses are kept along with the lines, since the text is truncated
+        let statuses = self.status_columns();
+        let mut lines: Vec<Vec<String>> = vec![];
+        let mut colors: Vec<Vec<Option<color::Color>>> = vec![];
+        for r in &self.rows {
+            let name = r
+                .object
+                .as_ref()
+                .and_then(|x| x.pointer("/metadata/name"))
+                .and_then(|x| x.as_str())
+                .unwrap_or("");
+            let mut line = vec![
+                r.context.clone(),
+                r.namespace.clone(),
+                r.kind.clone(),
+                name.to_string(),
+            ];
+            let mut color = vec![None; line.len()];
+            if explicit {
+                line.extend(r.values.iter().map(|x| x.to_string().replace('\n', " ")));
+                color.extend(Self::value_colors(r, &statuses));
+            }
+            lines.push(line);
+            colors.push(color);
+        }
+
+        let mut widths = titles
+            .iter()
+            .map(|x| x.chars().count())
+            .collect::<Vec<usize>>();
+        for line in &lines {
+            for (w, x) in widths.iter_mut().zip(line.iter()) {
+                *w = (*w).max(x.chars().count());
+            }
+        }
+
+        // every column is followed by 3 spaces
+        let terminal =
+            terminal_size::terminal_size().map(|(terminal_size::Width(w), _)| w as usize);
+        if let (true, Some(max)) = (truncate, terminal) {
+            let padding = 3 * widths.len();
+            while widths.iter().sum::<usize>() + padding > max {
+                let (i, w) = widths
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, w)| **w)
+                    .map(|(i, w)| (i, *w))
+                    .unwrap();
+                if w <= 8 {
+                    break;
+                }
+                widths[i] -= 1;
+            }
+        }
+
+        let mut table = Table::new();
+        table.set_format(Self::plain_format());
+        table.set_titles(Row::new(
+            titles
                 .iter()
-                .map(|x| Cell::from(x.1))
-                .collect::<Row>();
-            rows_deployment.insert_cell(0, Cell::new("deployment"));
-            row.push(rows_deployment);
+                .zip(widths.iter())
+                .map(|(x, w)| self.title_cell(&Self::truncate(x, *w)))
+                .collect::<Vec<Cell>>(),
+        ));
+        for (line, color) in lines.iter().zip(colors.iter()) {
+            table.add_row(Row::new(
+                line.iter()
+                    .zip(widths.iter())
+                    .zip(color.iter())
+                    .map(|((x, w), c)| self.value_cell(&Self::truncate(x, *w), *c))
+                    .collect::<Vec<Cell>>(),
+            ));
         }
 
-        Table::init(row).printstd();
+        table.printstd();
+    }
+
+    /// Prints the rows in the given format
+    pub fn print(self) {
+        match self.format {
+            Format::Table => self.print_wide(true),
+            Format::Wide => self.print_wide(false),
+            Format::Nested => self.print_table(),
+            Format::Json => self.print_json(),
+            Format::Ndjson => self.print_ndjson(),
+            Format::Csv => self.print_delimited(','),
+            Format::Tsv => self.print_delimited('\t'),
+            Format::Yaml => self.print_yaml(),
+            Format::Markdown => self.print_markdown(),
+            Format::Html => self.print_html(),
+            Format::JsonPath(ref t) => self.print_jsonpath(t),
+            Format::CustomColumns(ref c) => self.print_custom_columns(c),
+        }
+    }
+
+    fn print_table(self) {
+        if self
+            .rows
+            .iter()
+            .any(|x| x.context.is_empty() || x.namespace.is_empty() || x.kind.is_empty())
+        {
+            return self.print_flat();
+        }
+
+        print!("{}", Self::unmark(&self.nested().to_string()));
+    }
+
+    /// Returns a row per kind and a column per context, where each cell is a table of a
+    /// column per namespace. Kinds are in the order they are queried, contexts and namespaces
+    /// are in the order they are given.
+    fn nested(&self) -> Table {
+        let contexts = self.contexts.unwrap_or_default();
+        let namespaces = self.namespaces.unwrap_or_default();
+
+        // 1. Grouping the rows by their kinds, along with the queried kinds that have no rows
+
+        // Represents 'Kind - Rows' mapping, in the order of first appearance
+        let mut kind_rows: Vec<(String, Vec<&result::Row>)> = vec![];
+        let kinds = self
+            .queries
+            .unwrap_or_default()
+            .iter()
+            .map(|x| &x.kind)
+            .chain(self.rows.iter().map(|x| &x.kind));
+        for kind in kinds {
+            let kind = kind.to_lowercase();
+            if !kind_rows.iter().any(|(k, _)| *k == kind) {
+                kind_rows.push((kind, vec![]));
+            }
+        }
+        for r in &self.rows {
+            let kind = r.kind.to_lowercase();
+            if let Some((_, rows)) = kind_rows.iter_mut().find(|(k, _)| *k == kind) {
+                rows.push(r);
+            }
+        }
+
+        // 2. Creating the header row of the contexts
+
+        let mut table_rows: Vec<Row> = vec![];
+
+        let mut cs = contexts
+            .iter()
+            .map(|x| Cell::new(&self.mark(x, HEADER)))
+            .collect::<Vec<Cell>>();
+        match self.projections {
+            Some(p) if !p.is_empty() => cs.insert(
+                0,
+                Cell::new(&format!(
+                    "KIND / CONTEXT\n({})",
+                    p.iter()
+                        .map(|x| x.name.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                )),
+            ),
+            _ => cs.insert(0, Cell::new("KIND / CONTEXT")),
+        }
+        table_rows.push(Row::new(cs));
+
+        // 3. Creating a row per kind, a cell per context in the same order with the header
+
+        let statuses = self.status_columns();
+
+        for (kind, rows) in &kind_rows {
+            let mut cells = vec![Cell::new(&self.mark(kind, BOLD))];
+
+            for context in contexts {
+                let mut table_ctx = Table::new();
+                table_ctx.add_row(namespaces.iter().map(|x| Cell::new(x)).collect::<Row>());
+                table_ctx.add_row(
+                    namespaces
+                        .iter()
+                        .map(|ns| {
+                            let objects = rows
+                                .iter()
+                                .filter(|f| f.context == *context && f.namespace == *ns)
+                                .map(|x| self.describe(x, &statuses))
+                                .collect::<Vec<String>>()
+                                .join("\n");
+                            match objects.is_empty() {
+                                true => Cell::new("-"),
+                                false => Cell::new(&objects),
+                            }
+                        })
+                        .collect::<Row>(),
+                );
+                cells.push(Cell::from(&table_ctx));
+            }
+
+            table_rows.push(Row::new(cells));
+        }
+
+        Table::init(table_rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+    use crate::result::{build, default_projections, Entry};
+    use std::rc::Rc;
+
+    fn entry(context: &str, namespace: &str, kind: &str, name: &str) -> Entry {
+        Entry {
+            context: context.to_string(),
+            namespace: namespace.to_string(),
+            kind: kind.to_string(),
+            object: Rc::new(serde_json::json!({ "metadata": { "name": name } })),
+        }
+    }
+
+    fn render(sql: &str, entries: &[Entry]) -> String {
+        render_with(sql, entries, false)
+    }
+
+    /// Renders the nested tables, along with the escape codes of the colours if `color` is set
+    fn render_with(sql: &str, entries: &[Entry], color: bool) -> String {
+        let q = parser::parse_sql(sql, &Default::default());
+        let projections = match q.projections.is_empty() {
+            true => default_projections(),
+            false => q.projections.clone(),
+        };
+        let rows = build(entries, &projections, &q.group_by, &q.order_by, q.distinct);
+
+        let printer = Printer::builder()
+            .color(color)
+            .contexts(&q.contexts)
+            .namespaces(&q.namespaces)
+            .queries(&q.queries)
+            .projections(&q.projections)
+            .rows(rows);
+        Printer::unmark(&printer.nested().to_string())
+    }
+
+    #[test]
+    fn nested_cells_follow_the_context_header() {
+        let entries = vec![
+            entry("c", "default", "pod", "pod-c"),
+            entry("a", "default", "pod", "pod-a"),
+            entry("b", "kube-system", "pod", "pod-b"),
+        ];
+        let output = render(
+            "SELECT default, kube-system FROM a, b, c WHERE pod.status.phase = 'Running'",
+            &entries,
+        );
+
+        assert_eq!(
+            output,
+            "\
++----------------+---------------------------+---------------------------+---------------------------+
+| KIND / CONTEXT | a                         | b                         | c                         |
++----------------+---------------------------+---------------------------+---------------------------+
+| pod            | +---------+-------------+ | +---------+-------------+ | +---------+-------------+ |
+|                | | default | kube-system | | | default | kube-system | | | default | kube-system | |
+|                | +---------+-------------+ | +---------+-------------+ | +---------+-------------+ |
+|                | | pod-a   | -           | | | -       | pod-b       | | | pod-c   | -           | |
+|                | +---------+-------------+ | +---------+-------------+ | +---------+-------------+ |
++----------------+---------------------------+---------------------------+---------------------------+
+"
+        );
+    }
+
+    #[test]
+    fn nested_kinds_follow_the_query() {
+        let entries = vec![
+            entry("b", "default", "pod", "pod-b"),
+            entry("a", "default", "deployment", "deployment-a"),
+        ];
+        let output = render(
+            "SELECT default, metadata.name AS name FROM a, b \
+             WHERE deployment.metadata.name = 'deployment-a' OR pod.status.phase = 'Running'",
+            &entries,
+        );
+
+        assert_eq!(
+            output,
+            "\
++----------------+------------------+-------------+
+| KIND / CONTEXT | a                | b           |
+| (name)         |                  |             |
++----------------+------------------+-------------+
+| deployment     | +--------------+ | +---------+ |
+|                | | default      | | | default | |
+|                | +--------------+ | +---------+ |
+|                | | deployment-a | | | -       | |
+|                | +--------------+ | +---------+ |
++----------------+------------------+-------------+
+| pod            | +---------+      | +---------+ |
+|                | | default |      | | default | |
+|                | +---------+      | +---------+ |
+|                | | -       |      | | pod-b   | |
+|                | +---------+      | +---------+ |
++----------------+------------------+-------------+
+"
+        );
+    }
+
+    #[test]
+    fn delimited_fields_are_quoted_if_needed() {
+        assert_eq!(Printer::quote("pod-a", ','), "pod-a");
+        assert_eq!(Printer::quote("a,b", ','), "\"a,b\"");
+        assert_eq!(Printer::quote("a,b", '\t'), "a,b");
+        assert_eq!(Printer::quote("a\tb", '\t'), "\"a\tb\"");
+        assert_eq!(Printer::quote("say \"hi\"", ','), "\"say \"\"hi\"\"\"");
+        assert_eq!(Printer::quote("a\nb", ','), "\"a\nb\"");
+        assert_eq!(Printer::quote("", ','), "");
+    }
+
+    #[test]
+    fn nested_statuses_are_coloured_by_their_paths() {
+        let pod = |name: &str, phase: &str| Entry {
+            context: "minikube".to_string(),
+            namespace: "default".to_string(),
+            kind: "pod".to_string(),
+            object: Rc::new(serde_json::json!({
+                "metadata": { "name": name },
+                "status": { "phase": phase },
+            })),
+        };
+        let entries = vec![pod("Running", "Pending"), pod("web", "Running")];
+        let sql = "SELECT default, pod.metadata.name AS name, pod.status.phase AS phase \
+                   FROM minikube WHERE pod.status.phase = 'Running'";
+
+        let output = render_with(sql, &entries, true);
+        assert!(
+            output.contains("| Running, \x1b[33mPending\x1b[0m |"),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("| web, \x1b[32mRunning\x1b[0m     |"),
+            "{}",
+            output
+        );
+        assert!(output.contains("\x1b[1;36mminikube\x1b[0m"), "{}", output);
+        assert!(output.contains("\x1b[1mpod\x1b[0m"), "{}", output);
+
+        let plain = ["\x1b[0m", "\x1b[1m", "\x1b[1;36m", "\x1b[32m", "\x1b[33m"]
+            .iter()
+            .fold(output, |x, code| x.replace(code, ""));
+        assert_eq!(plain, render(sql, &entries));
+    }
+
+    #[test]
+    fn list_statuses_are_coloured_by_the_most_severe() {
+        let statuses = |texts: &[&str]| {
+            Printer::statuses_color(&texts.iter().map(|x| x.to_string()).collect::<Vec<String>>())
+        };
+        assert_eq!(statuses(&["True", "True"]), Some(color::GREEN));
+        assert_eq!(statuses(&["True", "Unknown", "False"]), Some(color::RED));
+        assert_eq!(statuses(&["10.0.0.1"]), None);
+        assert_eq!(Printer::builder().color(true).mark("çay", BOLD), "çay");
     }
 }
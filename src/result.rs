@@ -0,0 +1,526 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::evaluator;
+use crate::evaluator::Record;
+use crate::planner::{Expression, OrderBy, Projection, Segment};
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// An object that satisfies the query, along with where it comes from
+#[derive(Debug)]
+pub struct Entry {
+    pub context: String,
+    pub namespace: String,
+    pub kind: String,
+    pub object: Rc<serde_json::Value>,
+}
+
+/// A row of the result set. The metadata of an aggregated row is empty, unless all the objects
+/// in the group share the same one.
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub context: String,
+    pub namespace: String,
+    pub kind: String,
+    pub values: Vec<Value>,
+    /// The object that the row is projected from, unless it is aggregated from many
+    pub object: Option<Rc<serde_json::Value>>,
+}
+
+impl Entry {
+    pub fn record(&self) -> Record<'_> {
+        Record {
+            context: &self.context,
+            namespace: &self.namespace,
+            kind: &self.kind,
+            object: &self.object,
+        }
+    }
+}
+
+/// Returns the projections to use if there is no column given in SELECT
+pub fn default_projections() -> Vec<Projection> {
+    vec![Projection {
+        name: "name".to_string(),
+        expr: Expression::Column {
+            kind: None,
+            path: vec![
+                Segment::Field("metadata".to_string()),
+                Segment::Field("name".to_string()),
+            ],
+        },
+        unnest: false,
+    }]
+}
+
+/// Returns whether the rows can be built from each batch of entries independently, as they
+/// arrive, since nothing needs to be known about the other ones
+pub fn streamable(
+    projections: &[Projection],
+    group_by: &[Expression],
+    order_by: &[OrderBy],
+    distinct: bool,
+) -> bool {
+    group_by.is_empty()
+        && order_by.is_empty()
+        && !distinct
+        && !projections.iter().any(|x| x.expr.is_aggregate())
+}
+
+/// Builds the result set from the given entries by projecting, grouping, deduplicating (if
+/// `distinct` is set, each element of a list on its own) and ordering them
+pub fn build(
+    entries: &[Entry],
+    projections: &[Projection],
+    group_by: &[Expression],
+    order_by: &[OrderBy],
+    distinct: bool,
+) -> Vec<Row> {
+    let group_by = group_by
+        .iter()
+        .map(|x| resolve(x, projections))
+        .collect::<Vec<Expression>>();
+    let aggregate = !group_by.is_empty() || projections.iter().any(|x| x.expr.is_aggregate());
+
+    // Each row is built along with its sort keys
+    let mut rows: Vec<(Row, Vec<Value>)> = match aggregate {
+        true => build_groups(entries, projections, &group_by, order_by),
+        false => entries
+            .iter()
+            .flat_map(|e| {
+                let record = e.record();
+                let mut values = evaluator::project(projections, &record);
+                if distinct {
+                    values = values.into_iter().flat_map(flatten).collect();
+                }
+                values
+                    .into_iter()
+                    .map(|values| {
+                        let keys = order_by
+                            .iter()
+                            .map(|o| match column(&o.expr, projections) {
+                                Some(i) => values[i].clone(),
+                                None => evaluator::evaluate(&o.expr, &record),
+                            })
+                            .collect();
+                        let row = Row {
+                            context: e.context.clone(),
+                            namespace: e.namespace.clone(),
+                            kind: e.kind.clone(),
+                            values,
+                            object: Some(e.object.clone()),
+                        };
+                        (row, keys)
+                    })
+                    .collect::<Vec<(Row, Vec<Value>)>>()
+            })
+            .collect(),
+    };
+
+    if distinct {
+        rows = deduplicate(rows);
+    }
+
+    rows.sort_by(|(_, a), (_, b)| {
+        a.iter()
+            .zip(b.iter())
+            .zip(order_by.iter())
+            .map(|((x, y), o)| match o.asc {
+                true => sort_values(x, y),
+                false => sort_values(y, x),
+            })
+            .find(|x| *x != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+
+    rows.into_iter().map(|(row, _)| row).collect()
+}
+
+/// Removes the rows with the same values across all contexts and namespaces. The metadata of a
+/// row is cleared if it differs from the metadata of its duplicates.
+fn deduplicate(rows: Vec<(Row, Vec<Value>)>) -> Vec<(Row, Vec<Value>)> {
+    let mut unique: Vec<(Row, Vec<Value>)> = vec![];
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for (row, keys) in rows {
+        let key = row
+            .values
+            .iter()
+            .map(|x| x.key())
+            .collect::<Vec<String>>()
+            .join("\0");
+        match index.get(&key) {
+            Some(i) => {
+                let first = &mut unique[*i].0;
+                if first.context != row.context {
+                    first.context.clear();
+                }
+                if first.namespace != row.namespace {
+                    first.namespace.clear();
+                }
+                if first.kind != row.kind {
+                    first.kind.clear();
+                }
+                let same = match (&first.object, &row.object) {
+                    (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+                    _ => false,
+                };
+                if !same {
+                    first.object = None;
+                }
+            }
+            None => {
+                index.insert(key, unique.len());
+                unique.push((row, keys));
+            }
+        }
+    }
+
+    unique
+}
+
+/// Expands the lists among the given values to a row per combination of their elements, in
+/// order to deduplicate the elements instead of the whole lists
+fn flatten(values: Vec<Value>) -> Vec<Vec<Value>> {
+    values.into_iter().fold(vec![vec![]], |rows, value| {
+        let elements = value.into_elements();
+        rows.iter()
+            .flat_map(|row| {
+                elements.iter().map(move |x| {
+                    let mut row = row.clone();
+                    row.push(x.clone());
+                    row
+                })
+            })
+            .collect()
+    })
+}
+
+fn build_groups(
+    entries: &[Entry],
+    projections: &[Projection],
+    group_by: &[Expression],
+    order_by: &[OrderBy],
+) -> Vec<(Row, Vec<Value>)> {
+    if projections.iter().any(|x| x.unnest) {
+        panic!("UNNEST is not supported along with GROUP BY or aggregate functions!")
+    }
+
+    let records = entries.iter().map(|x| x.record()).collect::<Vec<Record>>();
+
+    // Groups are kept in the order they first appear
+    let mut groups: Vec<Vec<&Record>> = vec![];
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for r in &records {
+        let keys = group_by
+            .iter()
+            .map(|x| evaluator::evaluate(x, r))
+            .collect::<Vec<Value>>();
+        let key = keys
+            .iter()
+            .map(|x| x.key())
+            .collect::<Vec<String>>()
+            .join("\0");
+        let i = *index.entry(key).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[i].push(r);
+    }
+
+    // Aggregate functions without GROUP BY always return a single row
+    if groups.is_empty() && group_by.is_empty() {
+        groups.push(vec![]);
+    }
+
+    groups
+        .into_iter()
+        .map(|group| {
+            let values = projections
+                .iter()
+                .map(|p| evaluator::evaluate_group(&p.expr, &group))
+                .collect::<Vec<Value>>();
+            let keys = order_by
+                .iter()
+                .map(|o| match column(&o.expr, projections) {
+                    Some(i) => values[i].clone(),
+                    None => evaluator::evaluate_group(&o.expr, &group),
+                })
+                .collect();
+            let row = Row {
+                context: uniform(&group, |r| r.context),
+                namespace: uniform(&group, |r| r.namespace),
+                kind: uniform(&group, |r| r.kind),
+                values,
+                object: None,
+            };
+            (row, keys)
+        })
+        .collect()
+}
+
+/// Returns the metadata shared by all records of the group, or empty if it differs
+fn uniform<'a>(group: &[&Record<'a>], f: impl Fn(&Record<'a>) -> &'a str) -> String {
+    match group.first().map(|x| f(x)) {
+        Some(first) if group.iter().all(|x| f(x) == first) => first.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Returns the index of the projection that the given ORDER BY or GROUP BY expression refers
+/// to, either by its alias (i.e. 'ORDER BY age') or its position (i.e. 'ORDER BY 1')
+fn column(expr: &Expression, projections: &[Projection]) -> Option<usize> {
+    match expr {
+        Expression::Column { kind: None, path } if path.len() == 1 => match &path[0] {
+            Segment::Field(f) => projections.iter().position(|p| p.name == *f),
+            _ => None,
+        },
+        Expression::Literal(Value::Integer(i)) if *i >= 1 && (*i as usize) <= projections.len() => {
+            Some(*i as usize - 1)
+        }
+        _ => None,
+    }
+}
+
+/// Replaces the reference to a projection with the expression of it
+fn resolve(expr: &Expression, projections: &[Projection]) -> Expression {
+    match column(expr, projections) {
+        Some(i) => projections[i].expr.clone(),
+        None => expr.clone(),
+    }
+}
+
+/// Orders the values ascending, where NULLs are the last, i.e. the first in descending order
+fn sort_values(a: &Value, b: &Value) -> Ordering {
+    match (a.is_null(), b.is_null()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        _ => a
+            .compare(b)
+            .unwrap_or_else(|| a.to_string().cmp(&b.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+    use serde_json::json;
+
+    fn pod(namespace: &str, name: &str, images: &[&str]) -> Entry {
+        Entry {
+            context: "minikube".to_string(),
+            namespace: namespace.to_string(),
+            kind: "pod".to_string(),
+            object: Rc::new(json!({
+                "metadata": { "name": name },
+                "spec": {
+                    "containers": images.iter().map(|x| json!({ "image": x })).collect::<Vec<_>>()
+                },
+            })),
+        }
+    }
+
+    /// Returns the values of the rows of the given query as text
+    fn rows(sql: &str, entries: &[Entry]) -> Vec<Vec<String>> {
+        let q = parser::parse_sql(sql, &Default::default());
+        build(
+            entries,
+            &q.projections,
+            &q.group_by,
+            &q.order_by,
+            q.distinct,
+        )
+        .into_iter()
+        .map(|r| r.values.iter().map(|x| x.to_string()).collect())
+        .collect()
+    }
+
+    fn pods() -> Vec<Entry> {
+        vec![
+            pod("default", "a", &["nginx", "redis"]),
+            pod("default", "b", &["nginx"]),
+            pod("kube-system", "c", &[]),
+        ]
+    }
+
+    #[test]
+    fn distinct_deduplicates_the_elements_of_lists() {
+        assert_eq!(
+            rows(
+                "SELECT DISTINCT default, kube-system, pod.spec.containers.image FROM minikube WHERE pod.status.phase = 'Running'",
+                &pods(),
+            ),
+            vec![vec!["nginx"], vec!["redis"], vec!["<none>"]]
+        );
+    }
+
+    #[test]
+    fn distinct_deduplicates_the_unnested_elements() {
+        assert_eq!(
+            rows(
+                "SELECT DISTINCT default, kube-system, UNNEST(pod.spec.containers[*].image) AS image FROM minikube WHERE pod.status.phase = 'Running'",
+                &pods(),
+            ),
+            vec![vec!["nginx"], vec!["redis"]]
+        );
+    }
+
+    #[test]
+    fn distinct_aggregates_count_the_elements_of_lists() {
+        assert_eq!(
+            rows(
+                "SELECT default, kube-system, COUNT(DISTINCT pod.spec.containers.image) AS images, COUNT(pod.spec.containers.image) AS lists FROM minikube WHERE pod.status.phase = 'Running'",
+                &pods(),
+            ),
+            vec![vec!["2", "3"]]
+        );
+    }
+
+    fn node(context: &str, name: &str, node: Option<&str>, created: &str) -> Entry {
+        Entry {
+            context: context.to_string(),
+            namespace: "default".to_string(),
+            kind: "pod".to_string(),
+            object: Rc::new(json!({
+                "metadata": { "name": name, "creationTimestamp": created },
+                "spec": { "nodeName": node },
+            })),
+        }
+    }
+
+    fn nodes() -> Vec<Entry> {
+        vec![
+            node("b", "web-1", Some("node-2"), "2021-01-03T00:00:00Z"),
+            node("a", "web-2", None, "2021-01-01T00:00:00Z"),
+            node("a", "web-3", Some("node-1"), "2021-01-02T00:00:00Z"),
+            node("a", "web-4", Some("node-2"), "2021-01-04T00:00:00Z"),
+        ]
+    }
+
+    #[test]
+    fn groups_are_aggregated_in_the_order_they_appear() {
+        assert_eq!(
+            rows(
+                "SELECT default, pod.spec.nodeName AS node, COUNT(*) AS pods, MIN(pod.metadata.name) FROM a, b WHERE pod.status.phase = 'Running' GROUP BY node",
+                &nodes(),
+            ),
+            vec![
+                vec!["node-2", "2", "web-1"],
+                vec!["<none>", "1", "web-2"],
+                vec!["node-1", "1", "web-3"],
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_keep_the_metadata_they_share() {
+        let q = parser::parse_sql(
+            "SELECT default, _context, COUNT(*) FROM a, b WHERE pod.status.phase = 'Running' GROUP BY 1",
+            &Default::default(),
+        );
+        let rows = build(
+            &nodes(),
+            &q.projections,
+            &q.group_by,
+            &q.order_by,
+            q.distinct,
+        );
+        let metadata = rows
+            .iter()
+            .map(|r| {
+                (
+                    r.context.as_str(),
+                    r.namespace.as_str(),
+                    r.values[1].to_string(),
+                )
+            })
+            .collect::<Vec<(&str, &str, String)>>();
+        assert_eq!(
+            metadata,
+            vec![
+                ("b", "default", "1".to_string()),
+                ("a", "default", "3".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn rows_are_ordered_by_alias_position_or_expression() {
+        let names = |sql: &str| {
+            rows(sql, &nodes())
+                .into_iter()
+                .map(|r| r[0].clone())
+                .collect::<Vec<String>>()
+        };
+        let select = "SELECT default, pod.metadata.name AS name, pod.spec.nodeName AS node FROM a, b WHERE pod.status.phase = 'Running'";
+
+        assert_eq!(
+            names(&format!("{} ORDER BY name DESC", select)),
+            vec!["web-4", "web-3", "web-2", "web-1"]
+        );
+        assert_eq!(
+            names(&format!("{} ORDER BY 2, 1 DESC", select)),
+            vec!["web-3", "web-4", "web-1", "web-2"]
+        );
+        assert_eq!(
+            names(&format!(
+                "{} ORDER BY pod.metadata.creationTimestamp ASC",
+                select
+            )),
+            vec!["web-2", "web-3", "web-1", "web-4"]
+        );
+    }
+
+    #[test]
+    fn nulls_are_the_largest_values() {
+        let select = "SELECT default, pod.spec.nodeName AS node FROM a, b WHERE pod.status.phase = 'Running'";
+        assert_eq!(
+            rows(&format!("{} ORDER BY node", select), &nodes()),
+            vec![
+                vec!["node-1"],
+                vec!["node-2"],
+                vec!["node-2"],
+                vec!["<none>"]
+            ]
+        );
+        assert_eq!(
+            rows(&format!("{} ORDER BY node DESC", select), &nodes()),
+            vec![
+                vec!["<none>"],
+                vec!["node-2"],
+                vec!["node-2"],
+                vec!["node-1"]
+            ]
+        );
+    }
+
+    #[test]
+    fn virtual_columns_are_the_metadata_of_the_objects() {
+        let values = rows(
+            "SELECT default, _context, _namespace, _kind, _age > INTERVAL '1 day' FROM a, b WHERE pod.metadata.name = 'web-1'",
+            &nodes()[..1],
+        );
+        assert_eq!(values, vec![vec!["b", "default", "pod", "true"]]);
+    }
+}
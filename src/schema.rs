@@ -0,0 +1,161 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::parser::ResourceType;
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+
+/// The deepest path that is resolved, in order not to follow recursive definitions forever
+const MAX_DEPTH: usize = 16;
+
+/// A node of the OpenAPI schema of a kind: i.e. the 'spec' of a Pod
+#[derive(Debug, Clone, Default)]
+pub struct Node {
+    /// The type of the values: i.e. 'string', 'object', or '[]object' for arrays of objects
+    pub type_name: String,
+    pub description: String,
+    pub properties: BTreeMap<String, Node>,
+    /// The elements of an array
+    pub items: Option<Box<Node>>,
+}
+
+/// The OpenAPI schemas of the supported kinds, as served by the API server
+#[derive(Debug, Default)]
+pub struct Schema {
+    kinds: HashMap<String, Node>,
+}
+
+impl Schema {
+    /// Fetches the schemas from '/openapi/v2' of the API server, along with the size of the
+    /// response body
+    pub async fn fetch(client: &kube::Client) -> Result<(Schema, usize)> {
+        let request = http::Request::get("/openapi/v2").body(vec![])?;
+        let body = client.request_text(request).await?;
+        let document: serde_json::Value = serde_json::from_str(&body)?;
+        Ok((Schema::from_openapi(&document), body.len()))
+    }
+
+    /// Resolves the schemas of the supported kinds from the given OpenAPI v2 document
+    pub fn from_openapi(document: &serde_json::Value) -> Schema {
+        let definitions = &document["definitions"];
+        let kinds = ResourceType::all()
+            .iter()
+            .filter_map(|k| {
+                let definition = definitions.get(k.definition())?;
+                Some((k.to_string(), resolve(definition, definitions, 0)))
+            })
+            .collect();
+
+        Schema { kinds }
+    }
+
+    /// Returns the schema of the given kind: i.e. 'pod'
+    pub fn kind(&self, kind: &str) -> Option<&Node> {
+        self.kinds.get(&kind.to_lowercase())
+    }
+
+    /// Returns the node of the given field path of the given kind, arrays are expanded
+    /// implicitly: i.e. 'spec.containers.image' of 'pod'
+    pub fn get(&self, kind: &str, path: &str) -> Option<&Node> {
+        let mut node = self.kind(kind)?;
+        for field in path.split('.').filter(|x| !x.is_empty()) {
+            while let Some(items) = &node.items {
+                node = items;
+            }
+            node = node.properties.get(field)?;
+        }
+        Some(node)
+    }
+
+    /// Returns the field paths of the given kind, arrays are expanded implicitly: i.e.
+    /// 'spec.containers.image'
+    pub fn paths(&self, kind: &str) -> Vec<String> {
+        let mut paths = vec![];
+        if let Some(node) = self.kind(kind) {
+            collect(node, "", &mut paths);
+        }
+        paths
+    }
+}
+
+fn collect(node: &Node, prefix: &str, paths: &mut Vec<String>) {
+    if let Some(items) = &node.items {
+        return collect(items, prefix, paths);
+    }
+    for (name, child) in &node.properties {
+        let path = match prefix.is_empty() {
+            true => name.clone(),
+            false => format!("{}.{}", prefix, name),
+        };
+        paths.push(path.clone());
+        collect(child, &path, paths);
+    }
+}
+
+/// Resolves the given schema into a node, following the '$ref's into the definitions
+fn resolve(schema: &serde_json::Value, definitions: &serde_json::Value, depth: usize) -> Node {
+    if depth > MAX_DEPTH {
+        return Node::default();
+    }
+
+    let description = schema["description"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+
+    if let Some(r) = schema["$ref"].as_str() {
+        let name = r.trim_start_matches("#/definitions/");
+        let mut node = match definitions.get(name) {
+            Some(d) => resolve(d, definitions, depth + 1),
+            None => Node::default(),
+        };
+        // the description of the field is more specific than the one of its definition
+        if !description.is_empty() {
+            node.description = description;
+        }
+        return node;
+    }
+
+    let properties: BTreeMap<String, Node> = schema["properties"]
+        .as_object()
+        .map(|p| {
+            p.iter()
+                .map(|(k, v)| (k.clone(), resolve(v, definitions, depth + 1)))
+                .collect()
+        })
+        .unwrap_or_default();
+    let items = schema
+        .get("items")
+        .filter(|x| x.is_object())
+        .map(|x| Box::new(resolve(x, definitions, depth + 1)));
+
+    let type_name = match (schema["type"].as_str(), &items) {
+        (Some("array"), Some(i)) => format!("[]{}", i.type_name),
+        (Some(t), _) => t.to_string(),
+        (None, _) if !properties.is_empty() => "object".to_string(),
+        (None, _) => String::new(),
+    };
+
+    Node {
+        type_name,
+        description,
+        properties,
+        items,
+    }
+}
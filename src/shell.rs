@@ -0,0 +1,270 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::executor::Executor;
+use crate::function;
+use crate::parser;
+use crate::parser::ResourceType;
+use crate::planner::VirtualColumn;
+use crate::schema::Schema;
+use anyhow::Result;
+use futures::FutureExt;
+use k8s_openapi::api::core::v1::Namespace;
+use kube::api::ListParams;
+use kube::Api;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+
+const KEYWORDS: [&str; 16] = [
+    "SELECT", "DISTINCT", "FROM", "WHERE", "AND", "OR", "NOT", "IS", "NULL", "GROUP", "ORDER",
+    "BY", "ASC", "DESC", "AS", "INTERVAL",
+];
+
+/// Completes the keywords, kinds, field paths, contexts and namespaces
+#[derive(Default)]
+struct ShellHelper {
+    contexts: Vec<String>,
+    namespaces: Vec<String>,
+    /// Represents 'Kind - Field Paths' mapping
+    paths: HashMap<String, Vec<String>>,
+}
+
+impl ShellHelper {
+    /// Returns the candidates that start with the given word
+    fn candidates(&self, word: &str) -> Vec<String> {
+        // 'pod.spec.' completes the field paths of the kind
+        if let Some((kind, path)) = word.split_once('.') {
+            return self
+                .paths
+                .get(&kind.to_lowercase())
+                .map(|paths| {
+                    paths
+                        .iter()
+                        .filter(|x| x.starts_with(path))
+                        .map(|x| format!("{}.{}", kind, x))
+                        .collect()
+                })
+                .unwrap_or_default();
+        }
+
+        let upper = word.to_uppercase();
+        let words = KEYWORDS
+            .iter()
+            .copied()
+            .chain(function::names())
+            .chain(["ANY", "UNNEST"])
+            .filter(|x| x.starts_with(&upper))
+            .map(|x| x.to_string());
+        let names = ResourceType::all()
+            .iter()
+            .map(|x| x.to_string())
+            .chain(VirtualColumn::all().iter().map(|x| x.to_string()))
+            .chain(self.contexts.iter().cloned())
+            .chain(self.namespaces.iter().cloned())
+            .filter(|x| x.starts_with(word));
+
+        let mut candidates = words.chain(names).collect::<Vec<String>>();
+        candidates.dedup();
+        candidates
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos]
+            .rfind(|c: char| !(c.is_alphanumeric() || "_-.[]*".contains(c)))
+            .map_or(0, |i| i + 1);
+        let candidates = self
+            .candidates(&line[start..pos])
+            .into_iter()
+            .map(|x| Pair {
+                display: x.clone(),
+                replacement: x,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// Adds the namespaces of the contexts that are connected since the last call to the completions,
+/// along with the queried namespaces in case they cannot be listed, i.e. due to RBAC
+async fn add_namespaces(helper: &mut ShellHelper, executor: &Executor, listed: &mut Vec<String>) {
+    for api in executor.apis() {
+        if !listed.contains(api.get_context()) {
+            listed.push(api.get_context().clone());
+            let namespaces: Api<Namespace> = Api::all(api.get_client().clone());
+            match namespaces.list(&ListParams::default()).await {
+                Ok(l) => {
+                    for name in l.items.into_iter().filter_map(|x| x.metadata.name) {
+                        if !helper.namespaces.contains(&name) {
+                            helper.namespaces.push(name);
+                        }
+                    }
+                }
+                Err(e) => eprintln!(
+                    "Could not list the namespaces of {:?} to complete them: {}",
+                    api.get_context(),
+                    e
+                ),
+            }
+        }
+        if !helper.namespaces.contains(api.get_namespace()) {
+            helper.namespaces.push(api.get_namespace().clone());
+        }
+    }
+}
+
+/// Returns the file that the history of the shell is kept in
+fn history_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|x| x.join(".kubesql_history"))
+}
+
+/// Runs the interactive shell until EOF or 'exit'. The queries can span multiple lines and
+/// are terminated by ';'.
+pub async fn run(mut executor: Executor) -> Result<()> {
+    let kubeconfig = parser::parse_kubeconfig(&executor.config().kubeconfigs);
+    let mut helper = ShellHelper {
+        contexts: kubeconfig.contexts.iter().map(|x| x.name.clone()).collect(),
+        ..Default::default()
+    };
+
+    // the field paths are completed from the schemas of the current context, if it is reachable,
+    // and the namespaces from the contexts as they are connected
+    let context = executor.config().context.clone();
+    let namespace = executor.config().namespace.clone();
+    let schema = match executor.api(context.as_deref(), &namespace).await {
+        Ok(i) => Schema::fetch(executor.apis()[i].get_client())
+            .await
+            .map(|(s, _)| s),
+        Err(e) => Err(e),
+    };
+    match schema {
+        Ok(schema) => {
+            for kind in ResourceType::all() {
+                let kind = kind.to_string();
+                helper.paths.insert(kind.clone(), schema.paths(&kind));
+            }
+        }
+        Err(e) => eprintln!("Could not fetch the schemas to complete field paths: {}", e),
+    }
+    let mut listed: Vec<String> = Vec::new();
+    add_namespaces(&mut helper, &executor, &mut listed).await;
+
+    let mut editor = Editor::<ShellHelper>::new();
+    editor.set_helper(Some(helper));
+    let history = history_path();
+    if let Some(h) = &history {
+        // there is no history on the first run
+        let _ = editor.load_history(h);
+    }
+
+    // panics are reported as errors of the query, without the location of the panic
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|info| {
+        match info.payload().downcast_ref::<String>() {
+            Some(s) => eprintln!("Error: {}", s),
+            None => match info.payload().downcast_ref::<&str>() {
+                Some(s) => eprintln!("Error: {}", s),
+                None => eprintln!("Error: {}", info),
+            },
+        }
+    }));
+
+    // the history is saved and the hook is restored on every exit, even if the input fails
+    let mut sql = String::new();
+    let result: Result<()> = loop {
+        let prompt = match sql.is_empty() {
+            true => "kubesql> ",
+            false => "      -> ",
+        };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                sql.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break Ok(()),
+            Err(e) => break Err(e.into()),
+        };
+
+        let trimmed = line.trim();
+        if sql.is_empty() && ["exit", "quit", "\\q"].contains(&trimmed) {
+            break Ok(());
+        }
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        sql.push_str(&line);
+        sql.push('\n');
+        if !trimmed.ends_with(';') {
+            continue;
+        }
+
+        editor.add_history_entry(sql.trim_end());
+        let query = sql.trim_end().trim_end_matches(';').to_string();
+        sql.clear();
+
+        // a panic is already reported by the hook
+        if let Ok(Err(e)) = AssertUnwindSafe(executor.execute(&query))
+            .catch_unwind()
+            .await
+        {
+            eprintln!("Error: {}", e);
+        }
+
+        if let Some(h) = editor.helper_mut() {
+            add_namespaces(h, &executor, &mut listed).await;
+        }
+    };
+
+    std::panic::set_hook(hook);
+    let saved = match &history {
+        Some(h) => editor.save_history(h),
+        None => Ok(()),
+    };
+
+    result?;
+    saved?;
+    Ok(())
+}
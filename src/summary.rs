@@ -0,0 +1,167 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use prettytable::format::FormatBuilder;
+use prettytable::{Cell, Row, Table};
+use std::time::{Duration, Instant};
+
+/// A call to the API server, along with what it returned
+#[derive(Debug)]
+pub struct Call {
+    pub context: String,
+    /// The namespace of the listed objects, or empty for the calls that are not namespaced
+    pub namespace: String,
+    /// The kind of the listed objects, or what else is fetched: i.e. 'openapi'
+    pub kind: String,
+    /// Number of the objects returned from the API server, if any are listed
+    pub listed: Option<usize>,
+    /// Number of the objects that satisfy the query, if the query is run against them
+    pub matched: Option<usize>,
+    /// Size of the response body
+    pub bytes: usize,
+    pub started: Instant,
+    pub elapsed: Duration,
+}
+
+/// Counts and timings of a query, printed after the results
+#[derive(Debug)]
+pub struct Summary {
+    started: Instant,
+    calls: Vec<Call>,
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Summary {
+            started: Instant::now(),
+            calls: vec![],
+        }
+    }
+}
+
+impl Summary {
+    pub fn new() -> Summary {
+        Summary::default()
+    }
+
+    /// Records the given API call
+    pub fn record(&mut self, call: Call) {
+        self.calls.push(call);
+    }
+
+    /// Prints the summary to stderr, in order not to mix it with the results
+    pub fn print(&self) {
+        let mut table = Table::new();
+        table.set_format(FormatBuilder::new().padding(0, 3).build());
+        table.set_titles(Row::new(
+            [
+                "CONTEXT",
+                "NAMESPACE",
+                "KIND",
+                "LISTED",
+                "MATCHED",
+                "BYTES",
+                "TIME",
+            ]
+            .iter()
+            .map(|x| Cell::new(x))
+            .collect(),
+        ));
+        for c in &self.calls {
+            table.add_row(Row::new(vec![
+                Cell::new(&c.context),
+                Cell::new(or_dash(&c.namespace)),
+                Cell::new(&c.kind),
+                Cell::new(&c.listed.map_or("-".to_string(), |x| x.to_string())),
+                Cell::new(&c.matched.map_or("-".to_string(), |x| x.to_string())),
+                Cell::new(&format_bytes(c.bytes)),
+                Cell::new(&format_elapsed(c.elapsed)),
+            ]));
+        }
+
+        // Represents 'Context - (API calls, Started, Finished)' mapping, in the order of first
+        // appearance. The calls run concurrently, so the time of a context is from the start of
+        // its first call to the end of its last one.
+        let mut clusters: Vec<(&str, usize, Instant, Instant)> = vec![];
+        for c in &self.calls {
+            let finished = c.started + c.elapsed;
+            match clusters.iter_mut().find(|(x, _, _, _)| *x == c.context) {
+                Some((_, calls, started, end)) => {
+                    *calls += 1;
+                    *started = (*started).min(c.started);
+                    *end = (*end).max(finished);
+                }
+                None => clusters.push((&c.context, 1, c.started, finished)),
+            }
+        }
+
+        let mut stderr = std::io::stderr();
+        eprintln!();
+        table
+            .print(&mut stderr)
+            .expect("Unable to write the summary");
+        eprintln!();
+        for (context, calls, started, finished) in clusters {
+            eprintln!(
+                "{}: {} API calls in {}",
+                context,
+                calls,
+                format_elapsed(finished - started)
+            );
+        }
+        eprintln!(
+            "Total: {} objects matched, {} API calls, {} transferred in {}",
+            self.calls.iter().filter_map(|x| x.matched).sum::<usize>(),
+            self.calls.len(),
+            format_bytes(self.calls.iter().map(|x| x.bytes).sum()),
+            format_elapsed(self.started.elapsed())
+        );
+    }
+}
+
+/// Returns a dash for the given empty text, in order not to leave the cell blank
+fn or_dash(text: &str) -> &str {
+    match text.is_empty() {
+        true => "-",
+        false => text,
+    }
+}
+
+/// Formats the given size in binary units: i.e. '12.3 KiB'
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+/// Formats the given duration in milliseconds or seconds: i.e. '350ms' or '1.25s'
+pub fn format_elapsed(elapsed: Duration) -> String {
+    match elapsed.as_millis() {
+        x if x < 1000 => format!("{}ms", x),
+        _ => format!("{:.2}s", elapsed.as_secs_f64()),
+    }
+}
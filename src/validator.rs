@@ -17,7 +17,41 @@
 // IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
 // CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
+use crate::evaluator::is_comparison;
+use crate::parser::ApiQueries;
+use crate::planner::{Expression, Segment};
+use crate::schema::{Node, Schema};
+use crate::value::Value;
 use kube::config::Kubeconfig;
+use std::str::FromStr;
+
+/// How the namespaces that are not found in their contexts are reported
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NamespaceCheck {
+    /// Fails before any object is listed
+    #[default]
+    Error,
+    /// Prints a warning and queries them anyway
+    Warn,
+    /// Does not list the namespaces at all
+    Skip,
+}
+
+impl FromStr for NamespaceCheck {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<NamespaceCheck, Self::Err> {
+        match input {
+            "error" => Ok(NamespaceCheck::Error),
+            "warn" => Ok(NamespaceCheck::Warn),
+            "skip" => Ok(NamespaceCheck::Skip),
+            _ => Err(format!(
+                "Invalid namespace check: '{}', one of: error, warn, skip",
+                input
+            )),
+        }
+    }
+}
 
 pub fn validate_contexts(kubeconfig: Kubeconfig, ctxs: &[String]) {
     let not_found = ctxs
@@ -32,3 +66,377 @@ pub fn validate_contexts(kubeconfig: Kubeconfig, ctxs: &[String]) {
         );
     }
 }
+
+/// Checks that the given namespaces exist in each of the given contexts, which are listed with
+/// their namespaces, and suggests the closest existing namespace for the missing ones
+pub fn validate_namespaces(
+    existing: &[(String, Vec<String>)],
+    nss: &[String],
+    check: NamespaceCheck,
+) {
+    let mut not_found: Vec<String> = Vec::new();
+    for (ctx, namespaces) in existing {
+        for ns in nss.iter().filter(|item| !namespaces.contains(item)) {
+            let message = match suggest(ns, namespaces) {
+                Some(s) => format!("{:?} in {:?}, did you mean {:?}?", ns, ctx, s),
+                None => format!("{:?} in {:?}", ns, ctx),
+            };
+            not_found.push(message);
+        }
+    }
+
+    if not_found.is_empty() {
+        return;
+    }
+    match check {
+        NamespaceCheck::Error => panic!("Given namespaces not found: {}", not_found.join("; ")),
+        NamespaceCheck::Warn => {
+            for message in not_found {
+                eprintln!("Warning: Given namespace not found: {}", message);
+            }
+        }
+        NamespaceCheck::Skip => {}
+    }
+}
+
+/// Returns the candidate that is the closest to the given name, if it is close enough to be a typo
+fn suggest<'a>(name: &str, candidates: &'a [String]) -> Option<&'a String> {
+    candidates
+        .iter()
+        .map(|c| (strsim::levenshtein(name, c), c))
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c)
+}
+
+/// Checks that the field paths of the given queries exist in the schemas of their kinds, and that
+/// they are not compared with literals of another type: i.e. 'pod.spec.hostNetwork = 'yes''. The
+/// paths without a kind are checked against each queried kind: i.e. 'metadata.name'
+pub fn validate_fields(schema: &Schema, api_queries: &ApiQueries) {
+    let kinds = api_queries
+        .queries
+        .iter()
+        .map(|x| x.kind.clone())
+        .collect::<Vec<String>>();
+    // ORDER BY and GROUP BY may refer to the projections by their names
+    let aliases = api_queries
+        .projections
+        .iter()
+        .map(|x| x.name.clone())
+        .collect::<Vec<String>>();
+
+    let mut errors: Vec<String> = Vec::new();
+    for q in &api_queries.queries {
+        let scope = Scope {
+            schema,
+            kinds: std::slice::from_ref(&q.kind),
+            aliases: &[],
+        };
+        validate_expression(&scope, &q.predicate, &mut errors);
+    }
+    let scope = Scope {
+        schema,
+        kinds: &kinds,
+        aliases: &[],
+    };
+    for p in &api_queries.projections {
+        validate_expression(&scope, &p.expr, &mut errors);
+    }
+    let scope = Scope {
+        aliases: &aliases,
+        ..scope
+    };
+    let expressions = api_queries
+        .group_by
+        .iter()
+        .chain(api_queries.order_by.iter().map(|x| &x.expr));
+    for expr in expressions {
+        validate_expression(&scope, expr, &mut errors);
+    }
+    errors.dedup();
+
+    if !errors.is_empty() {
+        panic!("Invalid fields: {}", errors.join("; "));
+    }
+}
+
+/// What the field paths are validated against
+struct Scope<'a> {
+    schema: &'a Schema,
+    /// The kinds of the paths that are not prefixed with a kind
+    kinds: &'a [String],
+    /// The names of the projections, which are not field paths
+    aliases: &'a [String],
+}
+
+fn validate_expression(scope: &Scope, expr: &Expression, errors: &mut Vec<String>) {
+    match expr {
+        Expression::Column { .. } => {
+            if let Err(e) = resolve_column(scope, expr) {
+                errors.push(e);
+            }
+        }
+        Expression::Function { args, .. } => {
+            for arg in args {
+                validate_expression(scope, arg, errors);
+            }
+        }
+        Expression::Aggregate { arg: Some(arg), .. } => validate_expression(scope, arg, errors),
+        Expression::BinaryOp { left, op, right } => {
+            validate_expression(scope, left, errors);
+            validate_expression(scope, right, errors);
+            if is_comparison(op) {
+                for (column, literal) in [(left, right), (right, left)] {
+                    if let Some(e) = validate_comparison(scope, column, literal) {
+                        errors.push(e);
+                    }
+                }
+            }
+        }
+        Expression::Any { expr, list, .. } => {
+            validate_expression(scope, expr, errors);
+            validate_expression(scope, list, errors);
+        }
+        Expression::Not(e) | Expression::IsNull { expr: e, .. } => {
+            validate_expression(scope, e, errors)
+        }
+        _ => {}
+    }
+}
+
+/// Returns the nodes of the given column, one per kind that it is resolved against. A path
+/// without a kind is valid if any of the queried kinds has it. Returns `None` if the type of the
+/// column is not known by the schemas: i.e. the keys of 'metadata.labels'
+fn resolve_column<'a>(
+    scope: &Scope<'a>,
+    column: &Expression,
+) -> Result<Option<Vec<&'a Node>>, String> {
+    let (kind, path) = match column {
+        Expression::Column { kind, path } => (kind, path),
+        _ => return Ok(None),
+    };
+    let kinds = match kind {
+        Some(k) => vec![k.as_str()],
+        None => match path.as_slice() {
+            [Segment::Field(f)] if scope.aliases.contains(f) => return Ok(None),
+            _ => scope.kinds.iter().map(|x| x.as_str()).collect(),
+        },
+    };
+
+    let mut nodes = Vec::new();
+    let mut error = None;
+    for k in kinds {
+        match resolve(scope.schema, k, kind.is_some(), path) {
+            Ok(Some(n)) => nodes.push(n),
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    match (nodes.is_empty(), error) {
+        (true, Some(e)) => Err(e),
+        _ => Ok(Some(nodes)),
+    }
+}
+
+/// Returns the node of the given field path of the given kind, or `None` if its fields are not
+/// known by the schema: i.e. the keys of 'metadata.labels'. Arrays are expanded implicitly.
+fn resolve<'a>(
+    schema: &'a Schema,
+    kind: &str,
+    qualified: bool,
+    path: &[Segment],
+) -> Result<Option<&'a Node>, String> {
+    let mut node = match schema.kind(kind) {
+        Some(n) => n,
+        None => return Ok(None),
+    };
+    // i.e. 'pod.spec.containers' or 'spec.containers', as given in the query
+    let name = |path: &[Segment]| {
+        let name = display(path);
+        match qualified {
+            true => format!("{}{}", kind, name),
+            false => name.trim_start_matches('.').to_string(),
+        }
+    };
+    for (i, segment) in path.iter().enumerate() {
+        let field = match segment {
+            Segment::Field(f) => f,
+            Segment::Index(_) | Segment::Wildcard => match &node.items {
+                Some(items) => {
+                    node = items;
+                    continue;
+                }
+                // the values of an object, i.e. 'metadata.labels[*]'
+                None if is_any(node)
+                    || (segment == &Segment::Wildcard && node.type_name == "object") =>
+                {
+                    return Ok(None)
+                }
+                None => {
+                    return Err(format!(
+                        "{} is of type {}, it is not an array",
+                        name(&path[..i]),
+                        node.type_name
+                    ))
+                }
+            },
+        };
+        while let Some(items) = &node.items {
+            node = items;
+        }
+        // a map or a field of any type, i.e. 'metadata.labels'
+        if is_any(node) {
+            return Ok(None);
+        }
+        if node.properties.is_empty() {
+            return Err(format!(
+                "{} is of type {}, it has no field {}",
+                name(&path[..i]),
+                node.type_name,
+                field
+            ));
+        }
+        let candidates = node.properties.keys().cloned().collect::<Vec<String>>();
+        node = match node.properties.get(field) {
+            Some(n) => n,
+            None => {
+                let given = name(&path[..=i]);
+                return Err(match suggest(field, &candidates) {
+                    Some(s) => {
+                        let mut suggested = path[..=i].to_vec();
+                        suggested[i] = Segment::Field(s.clone());
+                        format!(
+                            "{} is not a field of {}, did you mean {}?",
+                            given,
+                            kind,
+                            name(&suggested)
+                        )
+                    }
+                    None => format!("{} is not a field of {}", given, kind),
+                });
+            }
+        };
+    }
+    Ok(Some(node))
+}
+
+/// Returns why the given column cannot be compared with the given literal, if it cannot. The
+/// strings are not checked, since they are compared with numbers as well: i.e. quantities.
+fn validate_comparison(scope: &Scope, column: &Expression, literal: &Expression) -> Option<String> {
+    let (path, value) = match (column, literal) {
+        (Expression::Column { path, .. }, Expression::Literal(v)) => (path, v),
+        _ => return None,
+    };
+    // the wildcards are compared as lists
+    if path.contains(&Segment::Wildcard) {
+        return None;
+    }
+    let nodes = resolve_column(scope, column).ok()??;
+    if nodes.is_empty() || nodes.iter().any(|x| is_comparable(&x.type_name, value)) {
+        return None;
+    }
+
+    Some(format!(
+        "{} is of type {}, it cannot be compared with {}",
+        column, nodes[0].type_name, literal
+    ))
+}
+
+/// Whether the values of the given type can be compared with the given literal. The arrays are
+/// compared element-wise: i.e. 'pod.metadata.finalizers = 'x''
+fn is_comparable(type_name: &str, value: &Value) -> bool {
+    if let Some(element) = type_name.strip_prefix("[]") {
+        return is_comparable(element, value);
+    }
+    match (type_name, value) {
+        (_, Value::Null) => true,
+        ("string", Value::Bool(_)) => false,
+        ("integer" | "number", Value::String(s)) => s.parse::<f64>().is_ok(),
+        ("integer" | "number", Value::Bool(_)) => false,
+        ("boolean", v) => v.as_bool().is_some(),
+        ("object", _) => false,
+        _ => true,
+    }
+}
+
+/// Whether the fields of the given node are not known: i.e. a map, or a field of any type
+fn is_any(node: &Node) -> bool {
+    node.properties.is_empty() && (node.type_name == "object" || node.type_name.is_empty())
+}
+
+fn display(path: &[Segment]) -> String {
+    path.iter().map(|x| x.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+    use serde_json::json;
+
+    fn validate(sql: &str) {
+        let schema = Schema::from_openapi(&json!({"definitions": {
+            "io.k8s.api.core.v1.Pod": {"properties": {
+                "metadata": {"properties": {
+                    "name": {"type": "string"},
+                    "finalizers": {"type": "array", "items": {"type": "string"}},
+                    "labels": {"type": "object", "additionalProperties": {"type": "string"}},
+                }},
+                "spec": {"properties": {
+                    "hostNetwork": {"type": "boolean"},
+                    "containers": {"type": "array", "items": {"properties": {
+                        "image": {"type": "string"},
+                        "args": {"type": "array", "items": {"type": "string"}},
+                    }}},
+                }},
+            }},
+        }}));
+        validate_fields(&schema, &parser::parse_sql(sql, &Default::default()));
+    }
+
+    #[test]
+    fn known_fields_are_valid() {
+        validate("SELECT default, metadata.name AS name FROM minikube WHERE pod.metadata.labels.app-name = 'x' AND pod.spec.containers[0].image = 'nginx' ORDER BY name");
+    }
+
+    #[test]
+    fn arrays_are_compared_element_wise() {
+        validate("SELECT default FROM minikube WHERE pod.metadata.finalizers = 'x' OR pod.spec.containers.args = 'y'");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "pod.spec.containers[0].imgae is not a field of pod, did you mean pod.spec.containers[0].image?"
+    )]
+    fn unknown_fields_are_suggested() {
+        validate("SELECT default FROM minikube WHERE pod.spec.containers[0].imgae = 'nginx'");
+    }
+
+    #[test]
+    #[should_panic(expected = "metadata.nmae is not a field of pod, did you mean metadata.name?")]
+    fn fields_without_kind_are_checked_against_the_queried_kinds() {
+        validate("SELECT default, metadata.nmae FROM minikube WHERE pod.metadata.name = 'a'");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "pod.spec.hostNetwork is of type boolean, it cannot be compared with 'yes'"
+    )]
+    fn mismatched_types_are_rejected() {
+        validate("SELECT default FROM minikube WHERE pod.spec.hostNetwork = 'yes'");
+    }
+
+    #[test]
+    fn strings_are_compared_with_the_types_they_are_coerced_to() {
+        validate("SELECT default FROM minikube WHERE pod.spec.hostNetwork = 'true' OR pod.spec.hostNetwork != 'false'");
+    }
+
+    #[test]
+    fn suggestions_are_close_names_only() {
+        let candidates = vec!["default".to_string(), "kube-system".to_string()];
+        assert_eq!(suggest("defualt", &candidates), Some(&candidates[0]));
+        assert_eq!(suggest("production", &candidates), None);
+    }
+}
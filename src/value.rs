@@ -0,0 +1,516 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use chrono::{DateTime, Duration, NaiveDate, SecondsFormat, Utc};
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Binary (power of two) and decimal (power of ten) suffixes of a Kubernetes quantity
+const QUANTITY_SUFFIXES: [(&str, f64); 15] = [
+    ("Ki", 1024_f64),
+    ("Mi", 1_048_576_f64),
+    ("Gi", 1_073_741_824_f64),
+    ("Ti", 1_099_511_627_776_f64),
+    ("Pi", 1_125_899_906_842_624_f64),
+    ("Ei", 1_152_921_504_606_846_976_f64),
+    ("n", 1e-9),
+    ("u", 1e-6),
+    ("m", 1e-3),
+    ("k", 1e3),
+    ("M", 1e6),
+    ("G", 1e9),
+    ("T", 1e12),
+    ("P", 1e15),
+    ("E", 1e18),
+];
+
+/// Units of a duration in seconds, i.e. '1 hour', '30m' or '2 days'
+const DURATION_UNITS: [(&[&str], i64); 7] = [
+    (&["s", "sec", "secs", "second", "seconds"], 1),
+    (&["m", "min", "mins", "minute", "minutes"], 60),
+    (&["h", "hr", "hrs", "hour", "hours"], 3600),
+    (&["d", "day", "days"], 86400),
+    (&["w", "week", "weeks"], 604_800),
+    (&["mon", "month", "months"], 2_592_000),
+    (&["y", "year", "years"], 31_536_000),
+];
+
+/// A typed value produced by evaluating an expression against an object.
+///
+/// Values of different types are coerced to each other when compared:
+/// * Strings are parsed as numbers or quantities when compared to numbers or quantities
+/// * Strings are parsed as RFC3339 timestamps when compared to timestamps
+/// * Strings are parsed as durations when compared to durations, integers as seconds
+/// * Two strings are compared as quantities if both are quantities, lexically otherwise
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Timestamp(DateTime<Utc>),
+    Duration(Duration),
+    Quantity(Quantity),
+    List(Vec<Value>),
+    Object(serde_json::Map<String, serde_json::Value>),
+}
+
+/// A Kubernetes resource quantity, i.e. '100m', '2Gi' or '1.5'
+#[derive(Debug, Clone)]
+pub struct Quantity {
+    /// The quantity as it was written
+    raw: String,
+
+    /// The quantity in base units, i.e. '100m' is 0.1
+    value: f64,
+}
+
+impl Quantity {
+    /// Parses the given string in the Kubernetes quantity format
+    pub fn parse(input: &str) -> Option<Quantity> {
+        let s = input.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '+' || c == '-'))
+            .unwrap_or(s.len());
+        let (number, suffix) = s.split_at(split);
+        let number = number.parse::<f64>().ok()?;
+
+        let multiplier = match suffix {
+            "" => 1_f64,
+            _ if suffix.len() > 1 && suffix.starts_with(['e', 'E']) => {
+                10_f64.powi(suffix[1..].parse::<i32>().ok()?)
+            }
+            _ => QUANTITY_SUFFIXES.iter().find(|(k, _)| *k == suffix)?.1,
+        };
+
+        Some(Quantity {
+            raw: s.to_string(),
+            value: number * multiplier,
+        })
+    }
+
+    /// Returns the quantity in base units
+    pub fn as_f64(&self) -> f64 {
+        self.value
+    }
+}
+
+/// Parses the given duration, i.e. '1 hour', '1h30m' or '90'. Numbers without unit are
+/// in terms of the given default unit, i.e. 'MINUTE' for `INTERVAL '5' MINUTE`.
+pub fn parse_duration(input: &str, default_unit: &str) -> Option<Duration> {
+    let mut seconds = 0_f64;
+    let mut rest = input.trim();
+    if rest.is_empty() {
+        return None;
+    }
+
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+            .unwrap_or(rest.len());
+        let number = rest[..split].parse::<f64>().ok()?;
+        rest = rest[split..].trim_start();
+
+        let split = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..split] {
+            "" => default_unit,
+            u => u,
+        }
+        .to_lowercase();
+        rest = rest[split..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+
+        let multiplier = DURATION_UNITS
+            .iter()
+            .find(|(names, _)| names.contains(&unit.as_str()))?
+            .1;
+        seconds += number * multiplier as f64;
+    }
+
+    Some(Duration::milliseconds((seconds * 1000_f64) as i64))
+}
+
+/// Parses the given RFC3339 timestamp, or a date as its midnight in UTC
+pub fn parse_timestamp(input: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(input)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|t| DateTime::from_naive_utc_and_offset(t, Utc))
+        })
+}
+
+impl PartialEq for Quantity {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl Value {
+    /// Returns whether the value is a `true` boolean
+    pub fn is_true(&self) -> bool {
+        matches!(self, Value::Bool(true))
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Returns a key that is the same for the equal values of the same type, in order to
+    /// group or deduplicate them
+    pub fn key(&self) -> String {
+        match self {
+            Value::Quantity(q) => format!("Quantity({})", q.as_f64()),
+            Value::List(v) => format!(
+                "List([{}])",
+                v.iter()
+                    .map(|x| x.key())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            v => format!("{:?}", v),
+        }
+    }
+
+    /// Returns the elements of a list, NULL if it is empty, or the value itself otherwise
+    pub fn into_elements(self) -> Vec<Value> {
+        match self {
+            Value::List(v) if v.is_empty() => vec![Value::Null],
+            Value::List(v) => v,
+            v => vec![v],
+        }
+    }
+
+    /// Returns the value as a timestamp, parsing RFC3339 strings if needed
+    pub fn as_timestamp(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Value::Timestamp(t) => Some(*t),
+            Value::String(s) => parse_timestamp(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a duration, integers are in terms of seconds
+    pub fn as_duration(&self) -> Option<Duration> {
+        match self {
+            Value::Duration(d) => Some(*d),
+            Value::Integer(i) => Some(Duration::seconds(*i)),
+            Value::Float(x) => Some(Duration::milliseconds((x * 1000_f64) as i64)),
+            Value::String(s) => parse_duration(s, "s"),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a number, parsing strings as quantities if needed
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(x) => Some(*x),
+            Value::Quantity(q) => Some(q.as_f64()),
+            Value::String(s) => Quantity::parse(s).map(|q| q.as_f64()),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a boolean, parsing 'true' and 'false' strings if needed
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            Value::String(s) => s.parse::<bool>().ok(),
+            _ => None,
+        }
+    }
+
+    /// Compares the values by coercing them to the same type. Returns `None` if either is NULL
+    /// or they are not comparable.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => None,
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::String(a), Value::String(b)) => match (self.as_f64(), other.as_f64()) {
+                (Some(x), Some(y)) => x.partial_cmp(&y),
+                _ => Some(a.cmp(b)),
+            },
+            (Value::Timestamp(_), _) | (_, Value::Timestamp(_)) => {
+                Some(self.as_timestamp()?.cmp(&other.as_timestamp()?))
+            }
+            (Value::Duration(_), _) | (_, Value::Duration(_)) => {
+                Some(self.as_duration()?.cmp(&other.as_duration()?))
+            }
+            (Value::Bool(_), _) | (_, Value::Bool(_)) => {
+                Some(self.as_bool()?.cmp(&other.as_bool()?))
+            }
+            (Value::List(_), _) | (_, Value::List(_)) => None,
+            (Value::Object(_), _) | (_, Value::Object(_)) => None,
+            _ => self.as_f64()?.partial_cmp(&other.as_f64()?),
+        }
+    }
+
+    /// Returns whether the values are equal. Strings are compared as they are, values of
+    /// different types are compared by coercing them to the same type.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::List(_), _)
+            | (_, Value::List(_))
+            | (Value::Object(_), _)
+            | (_, Value::Object(_)) => self == other,
+            _ => self.compare(other) == Some(Ordering::Equal),
+        }
+    }
+
+    /// Returns the value as an integer, truncating floats
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            Value::Float(x) => Some(*x as i64),
+            Value::String(s) => s.parse::<i64>().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Integer(a), Value::Float(b)) | (Value::Float(b), Value::Integer(a)) => {
+                *a as f64 == *b
+            }
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Timestamp(a), Value::Timestamp(b)) => a == b,
+            (Value::Duration(a), Value::Duration(b)) => a == b,
+            (Value::Quantity(a), Value::Quantity(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Object(a), Value::Object(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl From<&serde_json::Value> for Value {
+    fn from(v: &serde_json::Value) -> Self {
+        match v {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(s) => Value::String(s.clone()),
+            serde_json::Value::Array(a) => Value::List(a.iter().map(Value::from).collect()),
+            serde_json::Value::Object(o) => Value::Object(o.clone()),
+        }
+    }
+}
+
+impl From<&Value> for serde_json::Value {
+    fn from(v: &Value) -> Self {
+        match v {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Integer(i) => serde_json::Value::from(*i),
+            Value::Float(x) => serde_json::Number::from_f64(*x)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            Value::List(l) => serde_json::Value::Array(l.iter().map(Self::from).collect()),
+            Value::Object(o) => serde_json::Value::Object(o.clone()),
+            v => serde_json::Value::String(v.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "<none>"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Timestamp(t) => write!(f, "{}", t.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            Value::Duration(d) => write!(f, "{}", format_duration(d)),
+            Value::Quantity(q) => write!(f, "{}", q),
+            Value::List(l) => write!(
+                f,
+                "{}",
+                l.iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ),
+            Value::Object(o) => write!(f, "{}", serde_json::Value::Object(o.clone())),
+        }
+    }
+}
+
+/// Formats the given duration the way `kubectl` prints ages, i.e. '3d4h' or '12m5s'
+fn format_duration(d: &Duration) -> String {
+    let sign = if *d < Duration::zero() { "-" } else { "" };
+    let secs = d.num_seconds().abs();
+    let (days, hours, minutes, seconds) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}{}s", sign, seconds),
+        (0, 0, _) => format!("{}{}m{}s", sign, minutes, seconds),
+        (0, _, _) => format!("{}{}h{}m", sign, hours, minutes),
+        _ => format!("{}{}d{}h", sign, days, hours),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn quantities_are_in_base_units() {
+        let parse = |s| Quantity::parse(s).map(|q| q.as_f64());
+        assert_eq!(parse("1.5"), Some(1.5));
+        assert_eq!(parse("100m"), Some(0.1));
+        assert_eq!(parse("2Gi"), Some(2_147_483_648_f64));
+        assert_eq!(parse("3k"), Some(3000_f64));
+        assert_eq!(parse("1e3"), Some(1000_f64));
+        assert_eq!(parse(" 128Mi "), Some(134_217_728_f64));
+        assert_eq!(parse("1X"), None);
+        assert_eq!(parse("Gi"), None);
+        assert_eq!(parse("abc"), None);
+    }
+
+    #[test]
+    fn quantities_keep_their_text() {
+        assert_eq!(Quantity::parse(" 500m").unwrap().to_string(), "500m");
+        assert_eq!(
+            Quantity::parse("0.5").unwrap(),
+            Quantity::parse("500m").unwrap()
+        );
+    }
+
+    #[test]
+    fn durations_are_parsed_with_units() {
+        assert_eq!(parse_duration("90", "s"), Some(Duration::seconds(90)));
+        assert_eq!(parse_duration("5", "MINUTE"), Some(Duration::minutes(5)));
+        assert_eq!(parse_duration("1 hour", "s"), Some(Duration::hours(1)));
+        assert_eq!(parse_duration("1h30m", "s"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("2 days, 1h", "s"), Some(Duration::hours(49)));
+        assert_eq!(
+            parse_duration("1.5s", "s"),
+            Some(Duration::milliseconds(1500))
+        );
+        assert_eq!(parse_duration("", "s"), None);
+        assert_eq!(parse_duration("1 fortnight", "s"), None);
+        assert_eq!(parse_duration("h", "s"), None);
+    }
+
+    #[test]
+    fn strings_are_compared_as_numbers_if_both_are_quantities() {
+        assert_eq!(string("500m").compare(&string("1")), Some(Ordering::Less));
+        assert_eq!(
+            string("1Gi").compare(&string("900Mi")),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(string("10").compare(&string("9")), Some(Ordering::Greater));
+        assert_eq!(string("b").compare(&string("a")), Some(Ordering::Greater));
+        assert_eq!(string("10").compare(&string("a")), Some(Ordering::Less));
+    }
+
+    #[test]
+    fn strings_are_coerced_to_the_other_type() {
+        assert_eq!(
+            string("2").compare(&Value::Integer(10)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            string("1.5").compare(&Value::Float(1.5)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            string("2021-01-01").compare(&Value::Timestamp(
+                parse_timestamp("2021-01-01T00:00:00Z").unwrap()
+            )),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            string("1h").compare(&Value::Duration(Duration::minutes(30))),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            Value::Integer(60).compare(&Value::Duration(Duration::minutes(1))),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            string("true").compare(&Value::Bool(true)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(string("yes").compare(&Value::Bool(true)), None);
+        assert_eq!(string("a").compare(&Value::Integer(1)), None);
+    }
+
+    #[test]
+    fn nulls_and_objects_are_not_comparable() {
+        assert_eq!(Value::Null.compare(&Value::Null), None);
+        assert_eq!(Value::Integer(1).compare(&Value::Null), None);
+        assert_eq!(Value::List(vec![]).compare(&Value::Integer(1)), None);
+        assert_eq!(
+            Value::Object(Default::default()).compare(&string("a")),
+            None
+        );
+    }
+
+    #[test]
+    fn strings_are_equal_as_they_are() {
+        assert!(string("a").equals(&string("a")));
+        assert!(!string("1").equals(&string("1.0")));
+        assert!(!string("1000m").equals(&string("1")));
+        assert!(string("1.0").equals(&Value::Integer(1)));
+        assert!(Value::Integer(1).equals(&Value::Float(1.0)));
+        assert!(!Value::Null.equals(&Value::Null));
+    }
+
+    #[test]
+    fn lists_are_equal_element_wise() {
+        let list = Value::List(vec![string("a"), Value::Integer(1)]);
+        assert!(list.equals(&Value::List(vec![string("a"), Value::Integer(1)])));
+        assert!(!list.equals(&Value::List(vec![string("a")])));
+        assert!(!list.equals(&string("a")));
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright (c) 2021 Dentrax
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+use anyhow::Result;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use kube::api::{ListParams, WatchEvent};
+use kube::Api;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// The first delay before the objects are listed again after an error, which is doubled up to
+/// `MAX_BACKOFF` as long as the errors go on
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A change of the objects that are watched
+#[derive(Debug)]
+pub enum Change {
+    /// All the objects, as they are listed at the start or once the watch has expired
+    Listed(Vec<serde_json::Value>),
+    /// The object is added or modified
+    Applied(serde_json::Value),
+    /// The object is deleted
+    Deleted(serde_json::Value),
+}
+
+struct State<K> {
+    api: Api<K>,
+    params: ListParams,
+    /// The resource version to resume from, unless the objects need to be listed again
+    version: Option<String>,
+    events: Option<BoxStream<'static, kube::Result<WatchEvent<K>>>>,
+    /// Whether the objects have been listed once, after which the errors are retried
+    listed: bool,
+    backoff: Duration,
+}
+
+/// Returns the changes of the objects of the given API, listing them first and watching them
+/// afterwards. The watch is resumed from the last seen version whenever the server closes it,
+/// and the objects are listed again if that version is too old. Once they are listed, the errors
+/// are retried with a backoff instead of ending the watch, listing the objects again.
+pub fn watch<K>(api: Api<K>, params: ListParams) -> BoxStream<'static, Result<Change>>
+where
+    K: Clone + Debug + DeserializeOwned + Serialize + Send + 'static,
+{
+    let state = State {
+        api,
+        params,
+        version: None,
+        events: None,
+        listed: false,
+        backoff: MIN_BACKOFF,
+    };
+
+    futures::stream::unfold(state, |mut state| async move {
+        let change = next(&mut state).await;
+        Some((change, state))
+    })
+    .boxed()
+}
+
+async fn next<K>(state: &mut State<K>) -> Result<Change>
+where
+    K: Clone + Debug + DeserializeOwned + Serialize + Send + 'static,
+{
+    loop {
+        match try_next(state).await {
+            Ok(Some(change)) => {
+                state.listed = true;
+                state.backoff = MIN_BACKOFF;
+                return Ok(change);
+            }
+            Ok(None) => continue,
+            Err(e) if !state.listed => return Err(e),
+            Err(e) => {
+                eprintln!(
+                    "Warning: Watch failed, listing again in {}s: {}",
+                    state.backoff.as_secs(),
+                    e
+                );
+                tokio::time::sleep(state.backoff).await;
+                state.backoff = (state.backoff * 2).min(MAX_BACKOFF);
+                state.version = None;
+                state.events = None;
+            }
+        }
+    }
+}
+
+/// Returns the next change, or `None` if there is nothing to return yet: i.e. after a bookmark
+async fn try_next<K>(state: &mut State<K>) -> Result<Option<Change>>
+where
+    K: Clone + Debug + DeserializeOwned + Serialize + Send + 'static,
+{
+    let version = match &state.version {
+        Some(v) => v.clone(),
+        None => {
+            let objects = state.api.list(&state.params).await?;
+            state.version = objects.metadata.resource_version.clone();
+            state.events = None;
+            return Ok(Some(Change::Listed(
+                objects
+                    .items
+                    .iter()
+                    .map(|x| serde_json::to_value(x).unwrap())
+                    .collect(),
+            )));
+        }
+    };
+
+    if state.events.is_none() {
+        let events = state.api.watch(&state.params, &version).await?;
+        state.events = Some(events.boxed());
+    }
+
+    let event = match state.events.as_mut().unwrap().try_next().await? {
+        Some(e) => e,
+        None => {
+            // the server closes the watch after a while, so it is resumed from where it is left
+            state.events = None;
+            return Ok(None);
+        }
+    };
+
+    let change = match event {
+        WatchEvent::Added(o) | WatchEvent::Modified(o) => {
+            Change::Applied(serde_json::to_value(o).unwrap())
+        }
+        WatchEvent::Deleted(o) => Change::Deleted(serde_json::to_value(o).unwrap()),
+        WatchEvent::Bookmark(b) => {
+            state.version = Some(b.metadata.resource_version);
+            return Ok(None);
+        }
+        WatchEvent::Error(e) if e.code == 410 => {
+            // 410 Gone: the version is too old to resume from
+            state.version = None;
+            return Ok(None);
+        }
+        WatchEvent::Error(e) => return Err(kube::Error::Api(e).into()),
+    };
+
+    if let Change::Applied(o) | Change::Deleted(o) = &change {
+        if let Some(v) = o["metadata"]["resourceVersion"].as_str() {
+            state.version = Some(v.to_string());
+        }
+    }
+
+    Ok(Some(change))
+}